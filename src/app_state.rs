@@ -1,104 +1,408 @@
 use crate::{
+    bridge::{InputRect, WidgetMessage},
     cli::{QueryArgs, WidgetMetadataArgs},
-    utils::widget_filter,
-    widget::Widget,
+    monitors::list_monitors,
+    permissions::Capability,
+    services::{
+        compositor::{Compositor, COMPOSITOR_TOPIC},
+        network,
+    },
+    storage::{Storage, StorageChange, GLOBAL_NAMESPACE},
+    utils::{format_table, format_template, get_state_dir_path, widget_filter},
+    widget::{Widget, COLUMNS},
 };
 
 use gdk::prelude::ApplicationExt;
+use gdk::{Display, Monitor};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+// `{ id, visible }` of every widget shown or hidden
+pub const VISIBILITY_TOPIC: &str = "visibility";
 
 #[derive(Debug)]
 pub struct AppState {
     pub application: gtk::Application,
     pub widgets: Vec<Widget>,
-    pub api: async_std::channel::Sender<String>,
+    pub api: async_std::channel::Sender<WidgetMessage>,
+    // last value published on each topic, replayed to late subscribers
+    pub retained: HashMap<String, serde_json::Value>,
+    pub compositor: Option<Compositor>,
+    pub network: Option<gio::DBusConnection>,
+    pub storage: Storage,
+    // zoom applied on top of each widget's own
+    pub ui_scale: f64,
 }
 
 impl AppState {
-    pub fn new(application: gtk::Application, api: async_std::channel::Sender<String>) -> Self {
+    pub fn new(
+        application: gtk::Application,
+        api: async_std::channel::Sender<WidgetMessage>,
+    ) -> Self {
         Self {
             application,
             widgets: vec![],
             api,
+            retained: HashMap::new(),
+            compositor: None,
+            network: None,
+            storage: Storage::load(get_state_dir_path().join("storage.json")),
+            ui_scale: 1.0,
         }
     }
 
     pub fn add_widget(
         &mut self,
+        name: Option<String>,
         url: String,
         tags: Vec<String>,
-        metadata: WidgetMetadataArgs,
+        permissions: Vec<Capability>,
+        // applied in order, e.g. the `--metadata` of create before its other options
+        metadata: Vec<WidgetMetadataArgs>,
     ) -> String {
-        let mut widget = Widget::new(&self.application, url, tags, self.api.clone());
+        let mut widget = Widget::new(&self.application, url, tags, permissions, self.api.clone());
         let id = widget.id.to_owned();
+        widget.name = name;
 
         // update widget metadata
-        widget.update(&metadata);
+        widget.set_ui_scale(self.ui_scale);
+        let focused = self.focused_monitor();
+        metadata
+            .iter()
+            .for_each(|e| widget.update(e, focused.as_deref()));
+
+        // load the page once window.www is ready to be injected
+        widget.load();
 
         // add widget to config
         self.widgets.push(widget);
 
-        id
+        // `--monitor all` spawns a clone on every other monitor
+        self.place_widgets();
+
+        self.widgets
+            .iter()
+            .filter(|e| e.id == id || e.group.as_ref() == Some(&id))
+            .map(|e| e.id.as_ref())
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 
     pub fn update_widget(&mut self, query: &QueryArgs, metadata: WidgetMetadataArgs) -> String {
-        self.widgets
+        let focused = self.focused_monitor();
+        let matching = self.matching_widgets(query);
+
+        let ret = self
+            .widgets
             .iter_mut()
-            .filter(|w| widget_filter(w, &query))
+            .filter(|w| matching.contains(&w.id))
             .map(|e| {
-                e.update(&metadata);
-                e.id.as_ref()
+                e.update(&metadata, focused.as_deref());
+                e.id.to_owned()
             })
             .collect::<Vec<_>>()
-            .join("\n")
+            .join("\n");
+
+        if metadata.monitor.is_some() {
+            self.place_widgets();
+        }
+
+        ret
     }
 
     pub fn delete_widget(&mut self, query: &QueryArgs) -> String {
-        let mut ret = String::new();
+        let mut ret = vec![];
+        let matching = self.matching_widgets(query);
 
         self.widgets.retain(|w| {
-            if widget_filter(w, query) {
+            if matching.contains(&w.id) {
                 w.close();
-                ret.push_str(&w.id);
+                ret.push(w.id.to_owned());
                 false
             } else {
                 true
             }
         });
 
-        ret.to_string()
+        ret.join("\n")
     }
 
-    pub fn hide_widget(&self, query: &QueryArgs) -> String {
+    // the ids of the matching widgets, along with every clone of their `--monitor all` group
+    pub fn matching_widgets(&self, query: &QueryArgs) -> HashSet<String> {
+        let filter = widget_filter(query);
+        let groups = self
+            .widgets
+            .iter()
+            .filter(|w| filter(w))
+            .filter_map(|e| e.group.as_ref())
+            .collect::<HashSet<_>>();
+
         self.widgets
             .iter()
-            .filter(|w| widget_filter(w, &query))
+            .filter(|w| filter(w) || w.group.as_ref().is_some_and(|e| groups.contains(e)))
+            .map(|e| e.id.to_owned())
+            .collect()
+    }
+
+    pub fn hide_widget(&mut self, query: &QueryArgs) -> String {
+        self.set_visibility(query, |_| false)
+    }
+
+    pub fn show_widget(&mut self, query: &QueryArgs) -> String {
+        self.set_visibility(query, |_| true)
+    }
+
+    // hide the visible widgets among the matching ones and show the others
+    pub fn toggle_widget(&mut self, query: &QueryArgs) -> String {
+        self.set_visibility(query, |e| !e.visible)
+    }
+
+    // `visible` gives the new state of each matching widget and its clones, changes are sent to
+    // VISIBILITY_TOPIC
+    fn set_visibility(&mut self, query: &QueryArgs, visible: impl Fn(&Widget) -> bool) -> String {
+        let mut changes = vec![];
+        let matching = self.matching_widgets(query);
+
+        let ret = self
+            .widgets
+            .iter_mut()
+            .filter(|w| matching.contains(&w.id))
             .map(|e| {
-                e.hide();
+                let visible = visible(e);
+                if visible != e.visible {
+                    changes.push(serde_json::json!({ "id": e.id, "visible": visible }));
+                }
+                match visible {
+                    true => e.show(),
+                    false => e.hide(),
+                }
                 e.id.as_ref()
             })
             .collect::<Vec<_>>()
-            .join("\n")
+            .join("\n");
+
+        changes.iter().for_each(|e| self.emit(VISIBILITY_TOPIC, e));
+
+        ret
     }
 
-    pub fn show_widget(&self, query: &QueryArgs) -> String {
+    // the output the compositor reports as focused, for `--monitor focused`
+    pub fn focused_monitor(&self) -> Option<String> {
+        self.retained
+            .get(COMPOSITOR_TOPIC)?
+            .get("active_monitor")?
+            .as_str()
+            .map(|e| e.to_string())
+    }
+
+    // follow the set of monitors, re-homing widgets and keeping one clone per monitor for `all`
+    pub fn place_widgets(&mut self) {
+        let focused = self.focused_monitor();
+        let monitors = Display::default()
+            .map(|e| list_monitors(&e))
+            .unwrap_or_default();
+
+        // a group switched away from `all` keeps a single widget
+        let mut kept = HashSet::new();
+        self.widgets
+            .retain(|e| match (&e.group, e.metadata.monitor.as_deref()) {
+                (Some(group), monitor) if monitor != Some("all") => {
+                    if kept.insert(group.to_owned()) {
+                        true
+                    } else {
+                        e.close();
+                        false
+                    }
+                }
+                _ => true,
+            });
+
+        // widgets switched to or away from `all` start or leave their own group
+        self.widgets
+            .iter_mut()
+            .for_each(|e| match e.metadata.monitor.as_deref() {
+                Some("all") => {
+                    e.group.get_or_insert(e.id.to_owned());
+                }
+                _ => {
+                    e.group = None;
+                    e.pinned = None;
+                }
+            });
+
+        let groups = self
+            .widgets
+            .iter()
+            .filter_map(|e| e.group.to_owned())
+            .collect::<HashSet<_>>();
+
+        for group in groups {
+            let in_group = |e: &Widget| e.group.as_ref() == Some(&group);
+
+            // unpin clones whose monitor is gone
+            self.widgets
+                .iter_mut()
+                .filter(|e| in_group(e))
+                .filter(|e| e.pinned.as_ref().is_some_and(|e| !monitors.contains(e)))
+                .for_each(|e| e.pinned = None);
+
+            // cover every monitor, with a spare clone or a new one
+            for monitor in &monitors {
+                if self
+                    .widgets
+                    .iter()
+                    .any(|e| in_group(e) && e.pinned.as_ref() == Some(monitor))
+                {
+                    continue;
+                }
+
+                match self
+                    .widgets
+                    .iter()
+                    .position(|e| in_group(e) && e.pinned.is_none())
+                {
+                    Some(spare) => self.widgets[spare].pinned = Some(monitor.to_owned()),
+                    None => {
+                        let widget = self.clone_widget(&group, monitor, focused.as_deref());
+                        self.widgets.push(widget);
+                    }
+                }
+            }
+
+            // close spare clones, keeping one while no monitor is connected
+            let mut keep = !self
+                .widgets
+                .iter()
+                .any(|e| in_group(e) && e.pinned.is_some());
+
+            self.widgets.retain(|e| {
+                if !in_group(e) || e.pinned.is_some() {
+                    true
+                } else if keep {
+                    keep = false;
+                    true
+                } else {
+                    e.close();
+                    false
+                }
+            });
+        }
+
         self.widgets
+            .iter_mut()
+            .for_each(|e| e.place(focused.as_deref()));
+    }
+
+    // a new member of the group pinned to the monitor, set up like the existing ones
+    fn clone_widget(&self, group: &str, monitor: &Monitor, focused: Option<&str>) -> Widget {
+        let template = self
+            .widgets
             .iter()
-            .filter(|w| widget_filter(w, &query))
+            .find(|e| e.group.as_deref() == Some(group))
+            .unwrap();
+        let mut widget = Widget::new(
+            &self.application,
+            template.url.to_owned(),
+            template.tags.to_owned(),
+            template.permissions.to_owned(),
+            self.api.clone(),
+        );
+
+        widget.set_ui_scale(self.ui_scale);
+        widget.name = template.name.to_owned();
+        widget.group = Some(group.to_owned());
+        widget.pinned = Some(monitor.to_owned());
+        widget.update(&WidgetMetadataArgs::from(&template.metadata), focused);
+        widget.visible = template.visible;
+        widget.load();
+
+        widget
+    }
+
+    pub fn set_ui_scale(&mut self, scale: f64) -> String {
+        self.ui_scale = scale;
+        self.widgets.iter_mut().for_each(|e| e.set_ui_scale(scale));
+
+        scale.to_string()
+    }
+
+    pub fn fit_widget(&mut self, id: &str, width: i32, height: i32) -> String {
+        self.widgets
+            .iter_mut()
+            .filter(|w| w.id == id)
+            .map(|e| {
+                e.fit_content(width, height);
+                e.id.as_ref()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn set_input_region(&mut self, id: &str, rects: &Option<Vec<InputRect>>) -> String {
+        self.widgets
+            .iter_mut()
+            .filter(|w| w.id == id)
+            .map(|e| {
+                e.set_input_region(rects.as_deref());
+                e.id.as_ref()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn set_opaque_region(&mut self, id: &str, rects: &[InputRect]) -> String {
+        self.widgets
+            .iter_mut()
+            .filter(|w| w.id == id)
             .map(|e| {
-                e.show();
+                e.set_opaque_region(rects);
                 e.id.as_ref()
             })
             .collect::<Vec<_>>()
             .join("\n")
     }
 
+    // a table by default, or the serialized widgets, or a line per widget following the template
+    pub fn list_widgets(
+        &self,
+        query: &QueryArgs,
+        json: bool,
+        format: Option<&str>,
+    ) -> Result<String, String> {
+        let filter = widget_filter(query);
+        let widgets = self
+            .widgets
+            .iter()
+            .filter(|w| filter(w))
+            .collect::<Vec<_>>();
+
+        if json {
+            return Ok(serde_json::to_string_pretty(&widgets).unwrap());
+        }
+
+        let Some(template) = format else {
+            return Ok(format_table(
+                &COLUMNS,
+                &widgets.iter().map(|e| e.columns()).collect::<Vec<_>>(),
+            ));
+        };
+
+        widgets
+            .iter()
+            .map(|e| format_template(template, &e.columns(), &serde_json::to_value(e).unwrap()))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|e| e.join("\n"))
+    }
+
     pub fn inspect_widget(&self, query: &QueryArgs) -> String {
+        let filter = widget_filter(query);
+
         self.widgets
             .iter()
-            .filter(|w| widget_filter(w, &query))
+            .filter(|w| filter(w))
             .map(|e| {
                 e.inspect();
-                e.id.as_ref()
+                e.summary()
             })
             .collect::<Vec<_>>()
             .join("\n")
@@ -109,9 +413,11 @@ impl AppState {
     }
 
     pub fn reload_widget(&self, query: &QueryArgs) -> String {
+        let filter = widget_filter(query);
+
         self.widgets
             .iter()
-            .filter(|w| widget_filter(w, &query))
+            .filter(|w| filter(w))
             .map(|e| {
                 e.reload();
                 e.id.as_ref()
@@ -119,4 +425,184 @@ impl AppState {
             .collect::<Vec<_>>()
             .join("\n")
     }
+
+    pub fn subscribe_widget(&mut self, id: &str, topic: &str) -> String {
+        self.widgets
+            .iter_mut()
+            .filter(|w| w.id == id)
+            .map(|e| {
+                e.subscriptions.insert(topic.to_owned());
+                if let Some(detail) = self.retained.get(topic) {
+                    e.emit_retained(topic, detail);
+                }
+                e.id.as_ref()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn unsubscribe_widget(&mut self, id: &str, topic: &str) -> String {
+        self.widgets
+            .iter_mut()
+            .filter(|w| w.id == id)
+            .map(|e| {
+                e.subscriptions.remove(topic);
+                e.id.as_ref()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn clear_subscriptions(&mut self, id: &str) -> String {
+        self.widgets
+            .iter_mut()
+            .filter(|w| w.id == id)
+            .map(|e| {
+                e.subscriptions.clear();
+                e.id.as_ref()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn has_subscribers(&self, topic: &str) -> bool {
+        self.widgets.iter().any(|w| w.subscriptions.contains(topic))
+    }
+
+    // dispatch an event to every widget subscribed to the topic
+    pub fn emit<T: Serialize>(&self, topic: &str, detail: &T) {
+        self.widgets
+            .iter()
+            .filter(|w| w.subscriptions.contains(topic))
+            .for_each(|e| e.emit(topic, detail));
+    }
+
+    // like emit, but keep the value for widgets subscribing later
+    pub fn publish<T: Serialize>(&mut self, topic: &str, detail: &T) {
+        let detail = serde_json::to_value(detail).unwrap();
+        self.widgets
+            .iter()
+            .filter(|w| w.subscriptions.contains(topic))
+            .for_each(|e| e.emit_retained(topic, &detail));
+        self.retained.insert(topic.to_owned(), detail);
+    }
+
+    pub fn switch_workspace(&self, name: &str) -> String {
+        let Some(compositor) = self.compositor.clone() else {
+            return "no supported compositor detected".to_string();
+        };
+        let workspace = name.to_owned();
+
+        glib::spawn_future_local(async move {
+            if let Err(e) = compositor.switch_workspace(&workspace).await {
+                eprintln!("failed to switch workspace: {}", e);
+            }
+        });
+
+        name.to_string()
+    }
+
+    // widgets subscribe to bus channels as `bus:<channel>` topics
+    pub fn publish_message(
+        &mut self,
+        channel: &str,
+        data: &serde_json::Value,
+        retain: bool,
+    ) -> String {
+        let topic = format!("bus:{}", channel);
+
+        if retain {
+            self.publish(&topic, data);
+        } else {
+            self.emit(&topic, data);
+        }
+
+        self.widgets
+            .iter()
+            .filter(|w| w.subscriptions.contains(&topic))
+            .map(|e| e.id.as_ref())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    // answer a `Call` from the page, the bridge resolves the matching promise
+    pub fn reply(&self, id: &str, call: u64, result: &str) {
+        let detail = serde_json::json!({ "id": call, "result": result });
+
+        self.widgets
+            .iter()
+            .filter(|w| w.id == id)
+            .for_each(|e| e.emit("reply", &detail));
+    }
+
+    // every widget gets its own namespace keyed by its url, so it survives restarts
+    pub fn storage_namespace(&self, id: &str, global: bool) -> Option<String> {
+        if global {
+            return Some(GLOBAL_NAMESPACE.to_string());
+        }
+
+        self.widgets
+            .iter()
+            .find(|w| w.id == id)
+            .map(|e| e.url.to_owned())
+    }
+
+    pub fn get_storage(&self, namespace: &str, key: Option<&str>) -> String {
+        let value = match key {
+            Some(key) => self.storage.get(namespace, key),
+            None => self.storage.entries(namespace),
+        };
+
+        serde_json::to_string(&value).unwrap()
+    }
+
+    pub fn set_storage(&mut self, namespace: &str, key: &str, value: serde_json::Value) -> String {
+        self.storage.set(namespace, key, value.to_owned());
+
+        let change = StorageChange {
+            namespace,
+            key,
+            value: &value,
+        };
+
+        if namespace == GLOBAL_NAMESPACE {
+            self.emit("storage:global", &change);
+        } else {
+            self.widgets
+                .iter()
+                .filter(|w| w.url == namespace && w.subscriptions.contains("storage"))
+                .for_each(|e| e.emit("storage", &change));
+        }
+
+        key.to_string()
+    }
+
+    pub fn set_wireless_enabled(&self, enabled: bool) -> String {
+        let Some(bus) = self.network.clone() else {
+            return "networkmanager is not available".to_string();
+        };
+
+        glib::spawn_future_local(async move {
+            if let Err(e) = network::set_wireless_enabled(&bus, enabled).await {
+                eprintln!("failed to toggle wifi: {}", e);
+            }
+        });
+
+        enabled.to_string()
+    }
+
+    pub fn activate_connection(&self, connection: &str) -> String {
+        let Some(bus) = self.network.clone() else {
+            return "networkmanager is not available".to_string();
+        };
+        let name = connection.to_owned();
+
+        glib::spawn_future_local(async move {
+            if let Err(e) = network::activate_connection(&bus, &name).await {
+                eprintln!("failed to activate connection: {}", e);
+            }
+        });
+
+        connection.to_string()
+    }
 }
@@ -0,0 +1,189 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    cli::{CliCommands, QueryArgs, WidgetMetadataArgs},
+    permissions::Capability,
+};
+
+// a message posted by a webview through `window.webkit.messageHandlers.widget`
+#[derive(Debug)]
+pub struct WidgetMessage {
+    pub sender: String,
+    pub command: ScriptCommands,
+}
+
+// a rectangle in page coordinates
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct InputRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub enum BridgeCommands {
+    Subscribe {
+        topic: String,
+    },
+    Unsubscribe {
+        topic: String,
+    },
+    SwitchWorkspace {
+        name: String,
+    },
+    SetWirelessEnabled {
+        enabled: bool,
+    },
+    ActivateConnection {
+        connection: String,
+    },
+    Publish {
+        channel: String,
+        #[serde(default)]
+        data: serde_json::Value,
+        #[serde(default)]
+        retain: bool,
+    },
+    StorageGet {
+        key: Option<String>,
+        #[serde(default)]
+        global: bool,
+    },
+    StorageSet {
+        key: String,
+        #[serde(default)]
+        value: serde_json::Value,
+        #[serde(default)]
+        global: bool,
+    },
+    // the `Self` commands always target the widget which posted them
+    UpdateSelf {
+        metadata: WidgetMetadataArgs,
+    },
+    HideSelf,
+    CloseSelf,
+    // posted by the page while its window is auto sized
+    ContentSize {
+        width: i32,
+        height: i32,
+    },
+    // bounding boxes of the `data-www-interactive` elements, none when the page marks nothing
+    InputRegion {
+        rects: Option<Vec<InputRect>>,
+    },
+    // run a command and send its result back to the page as a `www:reply` event
+    Call {
+        id: u64,
+        command: Box<BridgeCommands>,
+    },
+}
+
+// pages may post either bridge commands or plain cli commands
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ScriptCommands {
+    Bridge(BridgeCommands),
+    Cli(CliCommands),
+    // sent by the daemon on behalf of a widget, never parsed from a page
+    #[serde(skip)]
+    Internal(InternalCommands),
+}
+
+#[derive(Debug)]
+pub enum InternalCommands {
+    // the opaque parts of the page, from snapshots taken while alpha passthrough is on
+    OpaqueRegion { rects: Vec<InputRect> },
+    // a new page is loading, the old one's subscriptions would keep background services running
+    LoadStarted,
+}
+
+impl BridgeCommands {
+    pub fn required_capability(&self) -> Option<Capability> {
+        match self {
+            BridgeCommands::SetWirelessEnabled { .. }
+            | BridgeCommands::ActivateConnection { .. } => Some(Capability::NetworkControl),
+            BridgeCommands::Call { command, .. } => command.required_capability(),
+            _ => None,
+        }
+    }
+}
+
+impl ScriptCommands {
+    // grants are checked when a message arrives, before it reaches the daemon state
+    pub fn required_capability(&self) -> Option<Capability> {
+        match self {
+            ScriptCommands::Bridge(command) => command.required_capability(),
+            // a created widget could be granted anything through `--allow` or its manifest
+            ScriptCommands::Cli(CliCommands::Kill)
+            | ScriptCommands::Cli(CliCommands::Init { .. })
+            | ScriptCommands::Cli(CliCommands::Create { .. }) => Some(Capability::GlobalCommands),
+            ScriptCommands::Cli(_) => Some(Capability::OtherWidgets),
+            ScriptCommands::Internal(_) => None,
+        }
+    }
+}
+
+pub trait BridgeCommand {
+    fn mutate(&self, sender: &str, config: &mut AppState) -> String;
+}
+
+impl BridgeCommand for BridgeCommands {
+    fn mutate(&self, sender: &str, config: &mut AppState) -> String {
+        match self {
+            BridgeCommands::Subscribe { topic } => config.subscribe_widget(sender, topic),
+            BridgeCommands::Unsubscribe { topic } => config.unsubscribe_widget(sender, topic),
+            BridgeCommands::SwitchWorkspace { name } => config.switch_workspace(name),
+            BridgeCommands::SetWirelessEnabled { enabled } => config.set_wireless_enabled(*enabled),
+            BridgeCommands::ActivateConnection { connection } => {
+                config.activate_connection(connection)
+            }
+            BridgeCommands::Publish {
+                channel,
+                data,
+                retain,
+            } => config.publish_message(channel, data, *retain),
+            BridgeCommands::StorageGet { key, global } => {
+                match config.storage_namespace(sender, *global) {
+                    Some(namespace) => config.get_storage(&namespace, key.as_deref()),
+                    None => "null".to_string(),
+                }
+            }
+            BridgeCommands::StorageSet { key, value, global } => {
+                match config.storage_namespace(sender, *global) {
+                    Some(namespace) => config.set_storage(&namespace, key, value.to_owned()),
+                    None => "null".to_string(),
+                }
+            }
+            BridgeCommands::UpdateSelf { metadata } => match metadata.validate() {
+                Ok(()) => config.update_widget(&QueryArgs::by_id(sender), metadata.to_owned()),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    e
+                }
+            },
+            BridgeCommands::HideSelf => config.hide_widget(&QueryArgs::by_id(sender)),
+            BridgeCommands::CloseSelf => config.delete_widget(&QueryArgs::by_id(sender)),
+            BridgeCommands::ContentSize { width, height } => {
+                config.fit_widget(sender, *width, *height)
+            }
+            BridgeCommands::InputRegion { rects } => config.set_input_region(sender, rects),
+            BridgeCommands::Call { id, command } => {
+                let result = command.mutate(sender, config);
+                config.reply(sender, *id, &result);
+                result
+            }
+        }
+    }
+}
+
+impl BridgeCommand for InternalCommands {
+    fn mutate(&self, sender: &str, config: &mut AppState) -> String {
+        match self {
+            InternalCommands::OpaqueRegion { rects } => config.set_opaque_region(sender, rects),
+            InternalCommands::LoadStarted => config.clear_subscriptions(sender),
+        }
+    }
+}
@@ -1,28 +1,137 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use gdk::Display;
+use regex::Regex;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::{app_state::AppState, utils::widget_filter};
+use crate::{
+    app_state::AppState,
+    constants::WIDGET_SERVER_URL,
+    length::Length,
+    monitors::describe_monitors,
+    permissions::{Capability, WidgetManifest},
+    utils::get_widget_dir_path,
+    widget::{Widget, WidgetMetadata},
+};
+
+// a css easing function, checked as it ends up in the `transition` style of the page
+fn parse_easing(s: &str) -> Result<String, String> {
+    let invalid = || {
+        "Invalid easing, possible values: [linear, ease, ease-in, ease-out, ease-in-out, cubic-bezier(x1, y1, x2, y2)]"
+            .to_string()
+    };
+
+    if matches!(
+        s,
+        "linear" | "ease" | "ease-in" | "ease-out" | "ease-in-out"
+    ) {
+        return Ok(s.to_string());
+    }
 
-fn parse_layer(s: &str) -> Result<String, String> {
-    match s {
-        "background" => Ok("background".to_string()),
-        "bottom" => Ok("bottom".to_string()),
-        "top" => Ok("top".to_string()),
-        "overlay" => Ok("overlay".to_string()),
-        _ => Err("Invalid layer, possible values: [background, bottom, top, overlay]".to_string()),
+    let points = s
+        .strip_prefix("cubic-bezier(")
+        .and_then(|e| e.strip_suffix(')'))
+        .ok_or_else(invalid)?
+        .split(',')
+        .map(|e| e.trim().parse::<f64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the x coordinates are times, within the transition
+    match points.as_slice() {
+        [x1, y1, x2, y2]
+            if [x1, y1, x2, y2].iter().all(|e| e.is_finite())
+                && (0.0..=1.0).contains(x1)
+                && (0.0..=1.0).contains(x2) =>
+        {
+            Ok(s.to_string())
+        }
+        _ => Err(invalid()),
     }
 }
 
-fn parse_keyboard_mode(s: &str) -> Result<String, String> {
-    match s {
-        "none" => Ok("none".to_string()),
-        "exclusive" => Ok("exclusive".to_string()),
-        "on-demand" => Ok("on-demand".to_string()),
-        _ => {
-            Err("Invalid keyboard mode, possible values: [none, exclusive, on-demand]".to_string())
-        }
+fn validate_zoom(zoom: f64) -> Result<f64, String> {
+    match zoom > 0.0 && zoom.is_finite() {
+        true => Ok(zoom),
+        false => Err("Invalid zoom, expected a number above 0".to_string()),
+    }
+}
+
+fn parse_zoom(s: &str) -> Result<f64, String> {
+    validate_zoom(s.parse().unwrap_or(f64::NAN))
+}
+
+fn parse_metadata(s: &str) -> Result<WidgetMetadataArgs, String> {
+    let metadata = serde_json::from_str::<WidgetMetadata>(s)
+        .map_err(|e| format!("Invalid metadata: {}", e))?;
+    let ret = WidgetMetadataArgs::from(&metadata);
+    ret.validate()?;
+
+    Ok(ret)
+}
+
+// layer shell settings, shared by the cli, the bridge and the serialized metadata
+#[derive(ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WidgetLayer {
+    Background,
+    Bottom,
+    Top,
+    // stays visible above fullscreen windows
+    Overlay,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WidgetAnchor {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WidgetKeyboardMode {
+    None,
+    Exclusive,
+    OnDemand,
+}
+
+// the axes a widget follows the size of its page content on
+#[derive(ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WidgetAutoSize {
+    None,
+    Width,
+    Height,
+    Both,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WidgetAutohideMode {
+    None,
+    // shrinks to a strip along the edge
+    Collapse,
+    // moves off-screen past the edge
+    Slide,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WidgetTransitionKind {
+    None,
+    Fade,
+    Slide,
+    Scale,
+}
+
+impl std::fmt::Display for WidgetLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
     }
 }
 
@@ -36,52 +145,234 @@ pub struct Cli {
     pub json: bool,
 }
 
-#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+#[derive(Args, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DaemonOptions {
+    /// root directory that procfs and sysfs are read from
+    #[clap(long = "system-root", default_value = "/")]
+    pub system_root: PathBuf,
+
+    /// interval between two system statistics samples in milliseconds
+    #[clap(long = "sys-interval", default_value = "1000")]
+    pub sys_interval: u64,
+
+    /// interval between two power supply polls in milliseconds
+    #[clap(long = "power-interval", default_value = "5000")]
+    pub power_interval: u64,
+
+    /// zoom applied on top of every widget's own, see the `scale` command
+    #[clap(long = "ui-scale", default_value = "1.0", value_parser = parse_zoom)]
+    pub ui_scale: f64,
+}
+
+// how names, urls, directories and monitors of a query are compared
+#[derive(ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchMode {
+    #[default]
+    Exact,
+    // `*` matches any run of characters, `?` a single one
+    Glob,
+    Regex,
+}
+
+// an empty query matches every widget, destructive commands want `--all` for that,
+// ids are kept apart from the fields of WidgetMetadataArgs, which `update` flattens next to it
+#[derive(Args, Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct QueryArgs {
+    /// the full id, or a prefix matching a single widget
     #[clap(short, long)]
     pub id: Option<String>,
 
+    #[clap(long)]
+    pub name: Option<String>,
+
     #[clap(short, long)]
     pub url: Option<String>,
 
+    /// directory of the widget, relative to ~/.config/www/
+    #[clap(long)]
+    pub directory: Option<String>,
+
+    /// widgets with any of these tags, see `--all-tags`
     #[clap(short, long)]
     pub tags: Option<Vec<String>>,
+
+    /// widgets with every one of `--tags`
+    #[clap(long = "all-tags", default_value = "false")]
+    #[serde(default)]
+    pub all_tags: bool,
+
+    #[clap(long)]
+    pub visible: Option<bool>,
+
+    /// layer the widget is on, named apart from `--layer` which `update` sets
+    #[clap(id = "in_layer", long = "in-layer", value_enum)]
+    pub layer: Option<WidgetLayer>,
+
+    /// connector of the monitor the widget is on, named apart from `--monitor` which `update` sets
+    #[clap(id = "on_monitor", long = "on-monitor")]
+    pub monitor: Option<String>,
+
+    /// how `--name`, `--url`, `--directory` and `--on-monitor` are compared
+    #[clap(id = "match_mode", long = "match", value_enum, default_value = "exact")]
+    #[serde(default)]
+    pub mode: MatchMode,
+
+    /// select the widgets not matching the rest of the query
+    #[clap(long, default_value = "false")]
+    #[serde(default)]
+    pub not: bool,
+
+    /// required by delete, update and reload when nothing else is given
+    #[clap(long, default_value = "false")]
+    #[serde(default)]
+    pub all: bool,
+}
+
+impl QueryArgs {
+    pub fn by_id(id: &str) -> Self {
+        Self {
+            id: Some(id.to_owned()),
+            ..Self::default()
+        }
+    }
+
+    pub fn by_name(name: &str) -> Self {
+        Self {
+            name: Some(name.to_owned()),
+            ..Self::default()
+        }
+    }
+
+    // whether the query selects widgets by anything at all
+    pub fn is_empty(&self) -> bool {
+        self.id.is_none()
+            && self.name.is_none()
+            && self.url.is_none()
+            && self.directory.is_none()
+            && self.tags.is_none()
+            && self.visible.is_none()
+            && self.layer.is_none()
+            && self.monitor.is_none()
+    }
+
+    // expand an id prefix to the full id, unless several widgets start with it
+    pub fn resolve(&self, widgets: &[Widget]) -> Result<Self, String> {
+        if self.mode == MatchMode::Regex {
+            for pattern in [&self.name, &self.url, &self.directory, &self.monitor]
+                .into_iter()
+                .flatten()
+            {
+                Regex::new(pattern).map_err(|e| format!("invalid regex {}: {}", pattern, e))?;
+            }
+        }
+
+        let Some(id) = self.id.as_deref() else {
+            return Ok(self.clone());
+        };
+        if widgets.iter().any(|e| e.id == id) {
+            return Ok(self.clone());
+        }
+
+        let matches = widgets
+            .iter()
+            .filter(|e| e.id.starts_with(id))
+            .map(|e| e.id.as_str())
+            .collect::<Vec<_>>();
+
+        match matches.as_slice() {
+            [] => Ok(self.clone()),
+            [matched] => Ok(Self {
+                id: Some(matched.to_string()),
+                ..self.clone()
+            }),
+            _ => Err(format!("ambiguous id {}: {}", id, matches.join(", "))),
+        }
+    }
 }
 
-#[derive(Parser, Debug, Clone, Serialize, Deserialize, PartialEq)]
+// lengths take pixels, a percentage of the monitor or a calc() expression, e.g. `calc(50% - 20)`
+#[derive(Parser, Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Default)]
 pub struct WidgetMargins {
     #[clap(long = "margin-top")]
-    pub top: Option<i32>,
+    pub top: Option<Length>,
 
     #[clap(long = "margin-right")]
-    pub right: Option<i32>,
+    pub right: Option<Length>,
 
     #[clap(long = "margin-bottom")]
-    pub bottom: Option<i32>,
+    pub bottom: Option<Length>,
 
     #[clap(long = "margin-left")]
-    pub left: Option<i32>,
+    pub left: Option<Length>,
 }
 
-#[derive(Parser, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Parser, Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Default)]
 pub struct WidgetDefaultSize {
     #[clap(long = "default-width")]
-    pub width: Option<i32>,
+    pub width: Option<Length>,
 
     #[clap(long = "default-height")]
-    pub height: Option<i32>,
+    pub height: Option<Length>,
 }
 
-#[derive(Args, Debug, Clone, Serialize, Deserialize, PartialEq)]
+// bounds of the window while it is sized to its content
+#[derive(Parser, Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct WidgetSizeLimits {
+    #[clap(long = "min-width")]
+    pub min_width: Option<i32>,
+
+    #[clap(long = "max-width")]
+    pub max_width: Option<i32>,
+
+    #[clap(long = "min-height")]
+    pub min_height: Option<i32>,
+
+    #[clap(long = "max-height")]
+    pub max_height: Option<i32>,
+}
+
+#[derive(Parser, Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct WidgetAutohide {
+    /// hide behind the anchored edge, `collapse` to a strip or `slide` off-screen
+    #[clap(long = "autohide", value_enum)]
+    pub mode: Option<WidgetAutohideMode>,
+
+    /// pixels left on screen while hidden, the strip revealing the widget on hover
+    #[clap(long = "autohide-size")]
+    pub size: Option<i32>,
+
+    /// milliseconds before hiding again once the pointer left
+    #[clap(long = "autohide-delay")]
+    pub delay: Option<u64>,
+}
+
+#[derive(Parser, Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct WidgetTransition {
+    /// played by show and hide, which return once it completes
+    #[clap(long = "transition", value_enum)]
+    pub kind: Option<WidgetTransitionKind>,
+
+    /// milliseconds
+    #[clap(long = "transition-duration")]
+    pub duration: Option<u64>,
+
+    /// a css easing function
+    #[clap(long = "transition-easing", value_parser = parse_easing)]
+    pub easing: Option<String>,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub struct WidgetMetadataArgs {
+    /// `primary`, `focused`, `all`, an index, a connector such as `DP-2`, or part of the manufacturer name
     #[clap(short, long)]
-    pub monitor: Option<i32>,
+    pub monitor: Option<String>,
 
-    #[clap(short, long, value_parser = parse_layer)]
-    pub layer: Option<String>,
+    #[clap(short, long, value_enum)]
+    pub layer: Option<WidgetLayer>,
 
-    #[clap(short, long)]
-    pub anchors: Option<Vec<String>>,
+    #[clap(short, long, value_enum)]
+    pub anchors: Option<Vec<WidgetAnchor>>,
 
     #[clap(flatten)]
     pub margins: Option<WidgetMargins>,
@@ -89,31 +380,94 @@ pub struct WidgetMetadataArgs {
     #[clap(flatten)]
     pub size: Option<WidgetDefaultSize>,
 
+    /// size the window to the page content along `width`, `height` or `both` axes
+    #[clap(long = "auto-size", value_enum)]
+    pub auto_size: Option<WidgetAutoSize>,
+
+    #[clap(flatten)]
+    pub size_limits: Option<WidgetSizeLimits>,
+
     #[clap(short, long)]
     pub click_through: Option<bool>,
 
+    /// pass pointer events through the fully transparent pixels of the page
+    #[clap(long)]
+    pub alpha_passthrough: Option<bool>,
+
     #[clap(short, long)]
     pub exclusive: Option<bool>,
 
-    #[clap(short, long = "keyboard-mode", value_parser = parse_keyboard_mode)]
-    pub keyboard_mode: Option<String>,
+    #[clap(flatten)]
+    pub autohide: Option<WidgetAutohide>,
+
+    #[clap(flatten)]
+    pub transition: Option<WidgetTransition>,
+
+    #[clap(short, long = "keyboard-mode", value_enum)]
+    pub keyboard_mode: Option<WidgetKeyboardMode>,
+
+    /// zoom level of the page
+    #[clap(long, value_parser = parse_zoom)]
+    pub zoom: Option<f64>,
+
+    /// scale the zoom by the density of the monitor, so text keeps its physical size
+    #[clap(long = "zoom-dpi")]
+    pub zoom_dpi: Option<bool>,
+
+    /// scale the zoom by the global ui scale, on by default
+    #[clap(long = "follow-ui-scale")]
+    pub follow_ui_scale: Option<bool>,
 }
 
-#[derive(Subcommand, Debug, Serialize, Deserialize)]
+impl WidgetMetadataArgs {
+    // the checks of the option parsers, for metadata deserialized from json instead,
+    // e.g. `--metadata` or posted by a page
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(zoom) = self.zoom {
+            validate_zoom(zoom)?;
+        }
+        if let Some(easing) = self.transition.as_ref().and_then(|e| e.easing.as_deref()) {
+            parse_easing(easing)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum CliCommands {
     // list
-    Init,
+    Init {
+        #[clap(flatten)]
+        options: DaemonOptions,
+    },
     Kill,
     Create {
         #[clap(flatten)]
         group: CreateUrlGroup,
 
+        /// a unique name to query the widget by instead of its id
+        #[clap(long)]
+        name: Option<String>,
+
+        /// replace the widget holding the name instead of failing
+        #[clap(long, default_value = "false", requires = "name")]
+        upsert: bool,
+
         #[clap(short, long)]
         tags: Vec<String>,
 
+        /// capabilities granted on top of the ones declared in the widget's manifest.json
+        #[clap(long, value_enum)]
+        allow: Vec<Capability>,
+
         #[clap(flatten)]
         metadata: WidgetMetadataArgs,
 
+        /// start from the `metadata` printed by `list --format '{metadata}'`, the other options override it
+        #[clap(long = "metadata", value_parser = parse_metadata)]
+        base: Option<WidgetMetadataArgs>,
+
         #[clap(short, long, default_value = "false")]
         show: bool,
     },
@@ -140,18 +494,73 @@ pub enum CliCommands {
         #[clap(flatten)]
         query: QueryArgs,
     },
+    /// hide the visible widgets among the matching ones and show the others
+    Toggle {
+        #[clap(flatten)]
+        query: QueryArgs,
+    },
+    /// print the matching widgets as a table: name, id, directory or url, tags, layer, monitor, visible, state
     List {
         #[clap(flatten)]
         query: QueryArgs,
+
+        /// print the full serialized widgets instead
+        #[clap(long, default_value = "false")]
+        json: bool,
+
+        /// print a line per widget, e.g. `{name} {id}`, columns or fields such as `{metadata.layer}`
+        #[clap(long, conflicts_with = "json")]
+        format: Option<String>,
     },
     Inspect {
         #[clap(flatten)]
         query: QueryArgs,
     },
+    Publish {
+        channel: String,
+
+        /// json value, anything that fails to parse is sent as a string
+        data: String,
+
+        /// keep the message for widgets subscribing later
+        #[clap(short, long, default_value = "false")]
+        retain: bool,
+    },
+    Get {
+        /// omit to print every key of the namespace
+        key: Option<String>,
+
+        /// `global` or the url of a widget
+        #[clap(short, long, default_value = "global")]
+        namespace: String,
+    },
+    Set {
+        key: String,
+
+        /// json value, anything that fails to parse is stored as a string, null removes the key
+        value: String,
+
+        /// `global` or the url of a widget
+        #[clap(short, long, default_value = "global")]
+        namespace: String,
+    },
+    /// set the global ui scale of widgets following it
+    Scale {
+        #[clap(value_parser = parse_zoom)]
+        factor: f64,
+    },
+    /// list connected monitors: index, connector, manufacturer, geometry, scale, refresh rate
+    Monitors,
+    /// print typescript definitions of window.www and the bridge messages, e.g. `types > www.d.ts`
+    Types {
+        /// print a json schema instead
+        #[clap(long, default_value = "false")]
+        schema: bool,
+    },
     Version,
 }
 
-#[derive(Debug, Args, Clone, Serialize, Deserialize)]
+#[derive(Debug, Args, Clone, Serialize, Deserialize, JsonSchema)]
 #[group(required = true, multiple = false)]
 pub struct CreateUrlGroup {
     #[clap(short, long)]
@@ -161,32 +570,107 @@ pub struct CreateUrlGroup {
     url: Option<String>,
 }
 
+impl CliCommands {
+    fn query_mut(&mut self) -> Option<&mut QueryArgs> {
+        match self {
+            CliCommands::Delete { query }
+            | CliCommands::Update { query, .. }
+            | CliCommands::Reload { query }
+            | CliCommands::Show { query }
+            | CliCommands::Hide { query }
+            | CliCommands::Toggle { query }
+            | CliCommands::List { query, .. }
+            | CliCommands::Inspect { query } => Some(query),
+            _ => None,
+        }
+    }
+
+    // the command with its id prefix expanded, see `QueryArgs::resolve`
+    pub fn resolve(&self, widgets: &[Widget]) -> Result<Self, String> {
+        let destructive = matches!(
+            self,
+            CliCommands::Delete { .. } | CliCommands::Update { .. } | CliCommands::Reload { .. }
+        );
+
+        let mut ret = self.clone();
+        if let Some(query) = ret.query_mut() {
+            if destructive && query.is_empty() && !query.all {
+                return Err("no widget selected, pass --all to act on every widget".to_string());
+            }
+            *query = query.resolve(widgets)?;
+        }
+
+        Ok(ret)
+    }
+
+    // how long the client waits for transitions started by the command
+    pub fn settle_time(&self, config: &AppState) -> Duration {
+        let Ok(command) = self.resolve(&config.widgets) else {
+            return Duration::ZERO;
+        };
+
+        match &command {
+            CliCommands::Show { query }
+            | CliCommands::Hide { query }
+            | CliCommands::Toggle { query } => {
+                let matching = config.matching_widgets(query);
+
+                config
+                    .widgets
+                    .iter()
+                    .filter(|e| matching.contains(&e.id))
+                    .map(|e| e.transition_duration())
+                    .max()
+                    .unwrap_or_default()
+            }
+            _ => Duration::ZERO,
+        }
+    }
+}
+
 pub trait CliCommand {
-    fn mutate(&self, config: &mut AppState) -> String;
+    // errors reach the cli as such, to print to stderr and exit with 1
+    fn mutate(&self, config: &mut AppState) -> Result<String, String>;
 }
 
 impl CliCommand for CliCommands {
-    fn mutate(&self, config: &mut AppState) -> String {
-        match self {
-            CliCommands::List { query } => config
-                .widgets
-                .iter()
-                .filter(|e| widget_filter(e, query))
-                .map(|e| e.id.as_ref())
-                .collect::<Vec<_>>()
-                .join("\n"),
+    fn mutate(&self, config: &mut AppState) -> Result<String, String> {
+        let command = self.resolve(&config.widgets)?;
+
+        let ret = match &command {
+            CliCommands::List {
+                query,
+                json,
+                format,
+            } => config.list_widgets(query, *json, format.as_deref())?,
             CliCommands::Create {
                 group,
+                name,
+                upsert,
                 tags,
+                allow,
                 metadata,
+                base,
                 show,
             } => {
+                metadata.validate()?;
+                if let Some(base) = base {
+                    base.validate()?;
+                }
+                if let Some(name) = name {
+                    let taken = config.widgets.iter().any(|e| e.name.as_ref() == Some(name));
+                    if taken && !*upsert {
+                        return Err(format!("a widget named {} already exists", name));
+                    }
+                }
+
                 let i = group.directory.as_ref();
                 let j = group.url.as_ref();
 
                 let url = match i {
                     Some(d) => format!(
-                        "http://localhost:8082/{}",
+                        "{}/{}",
+                        WIDGET_SERVER_URL,
                         Path::new(d.as_str())
                             .join("index.html")
                             .to_str()
@@ -196,13 +680,34 @@ impl CliCommand for CliCommands {
                     None => j.unwrap().to_owned(),
                 };
 
-                let ret = config.add_widget(url, tags.to_owned(), metadata.to_owned());
+                // directory widgets may declare the capabilities they need
+                let mut permissions = match i {
+                    Some(d) => WidgetManifest::load(&get_widget_dir_path().join(d))?.permissions,
+                    None => vec![],
+                };
+                for capability in allow {
+                    if !permissions.contains(capability) {
+                        permissions.push(*capability);
+                    }
+                }
+
+                // with `--upsert`, the new widget takes the place of the one holding the name
+                if let Some(name) = name {
+                    config.delete_widget(&QueryArgs::by_name(name));
+                }
+
+                let ret = config.add_widget(
+                    name.to_owned(),
+                    url,
+                    tags.to_owned(),
+                    permissions,
+                    base.iter().chain([metadata]).cloned().collect(),
+                );
 
+                // one id per clone with `--monitor all`
                 if *show {
-                    config.show_widget(&QueryArgs {
-                        id: Some(ret.clone()),
-                        url: None,
-                        tags: None,
+                    ret.lines().for_each(|e| {
+                        config.show_widget(&QueryArgs::by_id(e));
                     });
                 }
 
@@ -210,19 +715,128 @@ impl CliCommand for CliCommands {
             }
             CliCommands::Delete { query } => config.delete_widget(query),
             CliCommands::Update { query, metadata } => {
+                metadata.validate()?;
                 config.update_widget(query, metadata.to_owned())
             }
             CliCommands::Hide { query } => config.hide_widget(query),
             CliCommands::Show { query } => config.show_widget(query),
+            CliCommands::Toggle { query } => config.toggle_widget(query),
             CliCommands::Kill => {
                 config.kill_application();
                 "killed".to_string()
             }
             CliCommands::Inspect { query } => config.inspect_widget(query),
             CliCommands::Reload { query } => config.reload_widget(query),
+            CliCommands::Publish {
+                channel,
+                data,
+                retain,
+            } => {
+                let data = serde_json::from_str::<serde_json::Value>(data)
+                    .unwrap_or(serde_json::Value::String(data.to_owned()));
+                config.publish_message(channel, &data, *retain)
+            }
+            CliCommands::Get { key, namespace } => config.get_storage(namespace, key.as_deref()),
+            CliCommands::Set {
+                key,
+                value,
+                namespace,
+            } => {
+                let value = serde_json::from_str::<serde_json::Value>(value)
+                    .unwrap_or(serde_json::Value::String(value.to_owned()));
+                config.set_storage(namespace, key, value)
+            }
+            CliCommands::Scale { factor } => config.set_ui_scale(*factor),
+            CliCommands::Monitors => match Display::default() {
+                Some(display) => describe_monitors(&display)
+                    .iter()
+                    .map(|e| e.summary())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                None => "no display".to_string(),
+            },
             // CARGO_PKG_VERSION
             CliCommands::Version => env!("CARGO_PKG_VERSION").to_string(),
             _ => "not implemented".to_string(),
-        }
+        };
+
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn parses_easings() {
+        assert!(parse_easing("ease-in-out").is_ok());
+        assert!(parse_easing("cubic-bezier(0.4, 0, 0.2, 1)").is_ok());
+        assert!(parse_easing("cubic-bezier(0,-1.5,1,2.5)").is_ok());
+        assert!(parse_easing("cubic-bezier(a)").is_err());
+        assert!(parse_easing("cubic-bezier(1,2,3)").is_err());
+        assert!(parse_easing("cubic-bezier(1.5, 0, 0.2, 1)").is_err());
+        assert!(parse_easing("cubic-bezier(0, 0, 0, 1); color: red").is_err());
+        assert!(parse_easing("cubic-bezier(0, 0, 0, 1), opacity 1s)").is_err());
+        assert!(parse_easing("steps(4)").is_err());
+    }
+
+    #[test]
+    fn validates_posted_metadata() {
+        let metadata = |value| serde_json::from_value::<WidgetMetadataArgs>(value).unwrap();
+
+        assert!(metadata(serde_json::json!({ "zoom": 1.5 }))
+            .validate()
+            .is_ok());
+        assert!(metadata(serde_json::json!({ "zoom": 0 }))
+            .validate()
+            .is_err());
+        assert!(metadata(serde_json::json!({ "zoom": -1 }))
+            .validate()
+            .is_err());
+        assert!(
+            metadata(serde_json::json!({ "transition": { "easing": "ease" } }))
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            metadata(serde_json::json!({ "transition": { "easing": "cubic-bezier(a)" } }))
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn has_unique_arguments() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn sets_metadata_next_to_the_query() {
+        let cli = Cli::try_parse_from([
+            "www",
+            "update",
+            "--in-layer",
+            "top",
+            "--layer",
+            "overlay",
+            "--autohide",
+            "slide",
+            "--match",
+            "glob",
+        ])
+        .unwrap();
+
+        let CliCommands::Update { query, metadata } = cli.command else {
+            panic!("expected update");
+        };
+        assert_eq!(query.layer, Some(WidgetLayer::Top));
+        assert_eq!(query.mode, MatchMode::Glob);
+        assert_eq!(metadata.layer, Some(WidgetLayer::Overlay));
+        assert_eq!(
+            metadata.autohide.and_then(|e| e.mode),
+            Some(WidgetAutohideMode::Slide)
+        );
     }
 }
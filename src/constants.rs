@@ -1,2 +1,4 @@
 pub const SOCKET_PATH: &str = "/tmp/www.sock";
+// directory widgets are served from here, see `start_web_server`
+pub const WIDGET_SERVER_URL: &str = "http://localhost:8082";
 
@@ -0,0 +1,304 @@
+use std::fmt;
+use std::str::FromStr;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// pixels, a percentage of the monitor, or a calc() expression of both, e.g. `calc(50% - 20)`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Length {
+    Pixels(i32),
+    Expression(String),
+}
+
+// nested parentheses and signs a length may use, pages send them and the parser recurses on each
+const MAX_DEPTH: usize = 32;
+
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+    // the monitor width or height percentages are taken from
+    extent: f64,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&mut self) -> Option<u8> {
+        while self.input.get(self.position) == Some(&b' ') {
+            self.position += 1;
+        }
+        self.input.get(self.position).copied()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.peek();
+        let matched = self.input[self.position..].starts_with(token.as_bytes());
+        if matched {
+            self.position += token.len();
+        }
+        matched
+    }
+
+    // a rule nested in a sign or parentheses
+    fn nested(&mut self, rule: fn(&mut Self) -> Result<f64, String>) -> Result<f64, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!(
+                "nested deeper than {} at {}",
+                MAX_DEPTH, self.position
+            ));
+        }
+
+        self.depth += 1;
+        let ret = rule(self);
+        self.depth -= 1;
+
+        ret
+    }
+
+    // expression := term (("+" | "-") term)*
+    fn expression(&mut self) -> Result<f64, String> {
+        let mut ret = self.term()?;
+
+        loop {
+            if self.eat("+") {
+                ret += self.term()?;
+            } else if self.eat("-") {
+                ret -= self.term()?;
+            } else {
+                return Ok(ret);
+            }
+        }
+    }
+
+    // term := factor (("*" | "/") factor)*
+    fn term(&mut self) -> Result<f64, String> {
+        let mut ret = self.factor()?;
+
+        loop {
+            if self.eat("*") {
+                ret *= self.factor()?;
+            } else if self.eat("/") {
+                ret /= self.factor()?;
+            } else {
+                return Ok(ret);
+            }
+        }
+    }
+
+    // factor := "-" factor | "(" expression ")" | number ("%" | "px")?
+    fn factor(&mut self) -> Result<f64, String> {
+        if self.eat("-") {
+            return Ok(-self.nested(Self::factor)?);
+        }
+
+        if self.eat("(") {
+            let ret = self.nested(Self::expression)?;
+            return match self.eat(")") {
+                true => Ok(ret),
+                false => Err("missing )".to_string()),
+            };
+        }
+
+        self.peek();
+        let start = self.position;
+        while self
+            .input
+            .get(self.position)
+            .is_some_and(|e| e.is_ascii_digit() || *e == b'.')
+        {
+            self.position += 1;
+        }
+
+        let number = std::str::from_utf8(&self.input[start..self.position])
+            .unwrap()
+            .parse::<f64>()
+            .map_err(|_| format!("expected a number at {}", start))?;
+
+        if self.eat("%") {
+            Ok(number * self.extent / 100.0)
+        } else {
+            self.eat("px");
+            Ok(number)
+        }
+    }
+}
+
+impl Length {
+    // resolve against the width or height of the target monitor
+    pub fn resolve(&self, extent: i32) -> Result<i32, String> {
+        let expression = match self {
+            Length::Pixels(pixels) => return Ok(*pixels),
+            Length::Expression(expression) => expression.trim(),
+        };
+        let inner = expression
+            .strip_prefix("calc(")
+            .and_then(|e| e.strip_suffix(')'))
+            .unwrap_or(expression);
+
+        let mut parser = Parser {
+            input: inner.as_bytes(),
+            position: 0,
+            extent: extent as f64,
+            depth: 0,
+        };
+        let ret = parser.expression()?;
+
+        match parser.peek() {
+            None => Ok(ret.round() as i32),
+            Some(_) => Err(format!("unexpected input at {}", parser.position)),
+        }
+    }
+}
+
+impl FromStr for Length {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(pixels) = s.trim().parse::<i32>() {
+            return Ok(Length::Pixels(pixels));
+        }
+
+        let ret = Length::Expression(s.to_string());
+        ret.resolve(0)
+            .map_err(|e| format!("invalid length {}: {}", s, e))?;
+
+        Ok(ret)
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Length::Pixels(pixels) => write!(f, "{}", pixels),
+            Length::Expression(expression) => f.write_str(expression),
+        }
+    }
+}
+
+// pixels serialize as numbers, so pages can keep posting plain sizes
+impl Serialize for Length {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Length::Pixels(pixels) => serializer.serialize_i32(*pixels),
+            Length::Expression(expression) => serializer.serialize_str(expression),
+        }
+    }
+}
+
+struct LengthVisitor;
+
+impl<'de> Visitor<'de> for LengthVisitor {
+    type Value = Length;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number of pixels, a percentage or a calc() expression")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Length::Pixels(v as i32))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(Length::Pixels(v as i32))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(Length::Pixels(v.round() as i32))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        v.parse().map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for Length {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(LengthVisitor)
+    }
+}
+
+// a number of pixels or the string form, same as `Deserialize` accepts
+impl JsonSchema for Length {
+    fn schema_name() -> String {
+        "Length".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let mut ret = SchemaObject {
+            instance_type: Some(vec![InstanceType::Number, InstanceType::String].into()),
+            ..Default::default()
+        };
+        ret.metadata().description =
+            Some("pixels, a percentage of the monitor or a calc() expression".to_string());
+
+        ret.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(length: &str, extent: i32) -> Result<i32, String> {
+        length.parse::<Length>()?.resolve(extent)
+    }
+
+    #[test]
+    fn resolves_pixels() {
+        assert_eq!("20".parse::<Length>(), Ok(Length::Pixels(20)));
+        assert_eq!(resolve("20", 1920), Ok(20));
+        assert_eq!(resolve("20px", 1920), Ok(20));
+    }
+
+    #[test]
+    fn resolves_percentages_of_the_extent() {
+        assert_eq!(resolve("30%", 1920), Ok(576));
+        assert_eq!(resolve("30%", 1080), Ok(324));
+        assert_eq!(resolve("50%", 0), Ok(0));
+    }
+
+    #[test]
+    fn resolves_calc() {
+        assert_eq!(resolve("calc(50% - 20)", 1920), Ok(940));
+        assert_eq!(resolve("calc(100% - 2 * (10px + 5))", 1080), Ok(1050));
+        assert_eq!(resolve("calc(-10 + 25%)", 400), Ok(90));
+    }
+
+    #[test]
+    fn rejects_invalid_lengths() {
+        assert!("".parse::<Length>().is_err());
+        assert!("abc".parse::<Length>().is_err());
+        assert!("calc(50% -)".parse::<Length>().is_err());
+        assert!("(50%".parse::<Length>().is_err());
+        assert!("50% 20".parse::<Length>().is_err());
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        assert_eq!(
+            resolve(&format!("{}1{}", "(".repeat(32), ")".repeat(32)), 0),
+            Ok(1)
+        );
+        assert_eq!(resolve(&format!("{}1", "-".repeat(32)), 0), Ok(1));
+
+        // without the limit, these would overflow the daemon's stack
+        let parentheses = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert!(parentheses.parse::<Length>().is_err());
+        assert!(format!("{}1", "-".repeat(100_000))
+            .parse::<Length>()
+            .is_err());
+        assert!("(".repeat(100_000).parse::<Length>().is_err());
+    }
+
+    #[test]
+    fn keeps_pixels_as_numbers() {
+        assert_eq!(serde_json::to_value(Length::Pixels(20)).unwrap(), 20);
+        assert_eq!(
+            serde_json::from_value::<Length>(serde_json::json!("calc(50% - 20)")).unwrap(),
+            Length::Expression("calc(50% - 20)".to_string())
+        );
+    }
+}
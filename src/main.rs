@@ -4,7 +4,13 @@ mod http_server;
 mod utils;
 mod widget;
 mod app_state;
+mod bridge;
+mod length;
+mod monitors;
+mod permissions;
+mod schema;
 mod services;
+mod storage;
 
 use std::fs::File;
 
@@ -43,7 +49,7 @@ fn main() {
 
     // if init, start daemon
     match &cli.command {
-        CliCommands::Init => {
+        CliCommands::Init { options } => {
             // 1. daemonize
             daemonize_application();
 
@@ -52,11 +58,16 @@ fn main() {
 
             // 3. start gtk application, this will block the main thread, during starting it will
             //    also listen to the unix socket for commands
-            start_widget_application();
+            start_widget_application(options.to_owned());
 
             // 4. kill http server handle after gtk application is closed
             rt::System::new().block_on(server_handle.stop(true));
         }
+        // generated from the rust types, no daemon needed
+        CliCommands::Types { schema } => match schema {
+            true => println!("{}", schema::json_schema()),
+            false => print!("{}", schema::typescript_definitions()),
+        },
         _ => {
             // run async statements with actix async runtime
             rt::System::new().block_on(async {
@@ -64,12 +75,19 @@ fn main() {
                 let mut stream = UnixStream::connect(SOCKET_PATH).await.expect("daemon is not running");
                 let k = serde_json::to_string(&cli.command).unwrap().to_string();
 
+                write_socket_message(&mut stream, k.to_owned()).await;
+                let response = read_socket_response(&mut stream).await;
+
                 if cli.json {
-                    write_socket_message(&mut stream, k.to_owned()).await;
-                    println!("{}\n================================\n{}", k, read_socket_response(&mut stream).await);
-                } else {
-                    write_socket_message(&mut stream, k).await;
-                    println!("{}", read_socket_response(&mut stream).await);
+                    println!("{}\n================================", k);
+                }
+                // errors go to stderr, so scripts can tell them apart by the exit code
+                match serde_json::from_str::<Result<String, String>>(&response).expect("invalid response") {
+                    Ok(output) => println!("{}", output),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
                 }
             });
         },
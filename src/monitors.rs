@@ -0,0 +1,126 @@
+use gdk::{Display, Monitor, Rectangle};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize, JsonSchema, PartialEq)]
+pub struct MonitorGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema, PartialEq)]
+pub struct MonitorInfo {
+    pub index: i32,
+    pub connector: Option<String>,
+    pub manufacturer: Option<String>,
+    // on wayland gdk3 reports the connector here, see `monitor_connector`
+    pub model: Option<String>,
+    pub primary: bool,
+    // in logical pixels
+    pub geometry: MonitorGeometry,
+    pub workarea: MonitorGeometry,
+    pub scale_factor: i32,
+    // in hz
+    pub refresh_rate: f64,
+}
+
+impl From<Rectangle> for MonitorGeometry {
+    fn from(rectangle: Rectangle) -> Self {
+        Self {
+            x: rectangle.x(),
+            y: rectangle.y(),
+            width: rectangle.width(),
+            height: rectangle.height(),
+        }
+    }
+}
+
+// on wayland, gdk3 reports the connector of a monitor (e.g. `DP-2`) as its model,
+// the real model name is not exposed
+pub fn monitor_connector(monitor: &Monitor) -> Option<String> {
+    monitor.model().map(|e| e.to_string())
+}
+
+pub fn list_monitors(display: &Display) -> Vec<Monitor> {
+    (0..display.n_monitors())
+        .filter_map(|e| display.monitor(e))
+        .collect()
+}
+
+pub fn describe_monitors(display: &Display) -> Vec<MonitorInfo> {
+    list_monitors(display)
+        .iter()
+        .enumerate()
+        .map(|(index, e)| MonitorInfo {
+            index: index as i32,
+            connector: monitor_connector(e),
+            manufacturer: e.manufacturer().map(|e| e.to_string()),
+            model: e.model().map(|e| e.to_string()),
+            primary: e.is_primary(),
+            geometry: e.geometry().into(),
+            workarea: e.workarea().into(),
+            scale_factor: e.scale_factor(),
+            // gdk reports millihertz
+            refresh_rate: e.refresh_rate() as f64 / 1000.0,
+        })
+        .collect()
+}
+
+impl MonitorInfo {
+    // one line per monitor for the cli
+    pub fn summary(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}x{}+{}+{}\tx{}\t{:.2}Hz{}",
+            self.index,
+            self.connector.as_deref().unwrap_or("-"),
+            self.manufacturer.as_deref().unwrap_or("-"),
+            self.geometry.width,
+            self.geometry.height,
+            self.geometry.x,
+            self.geometry.y,
+            self.scale_factor,
+            self.refresh_rate,
+            if self.primary { "\tprimary" } else { "" },
+        )
+    }
+}
+
+// `primary`, `focused`, an index, a connector, or part of the manufacturer
+pub fn resolve_monitor(
+    display: &Display,
+    selector: &str,
+    focused: Option<&str>,
+) -> Option<Monitor> {
+    let monitors = list_monitors(display);
+
+    match selector {
+        // wayland has no primary monitor, fall back to the first one
+        "primary" => display.primary_monitor().or(monitors.first().cloned()),
+        // same as primary until the compositor reports which output has focus
+        "focused" => focused
+            .and_then(|e| resolve_monitor(display, e, None))
+            .or_else(|| resolve_monitor(display, "primary", None)),
+        _ => {
+            if let Ok(index) = selector.parse::<usize>() {
+                return monitors
+                    .get(index.min(monitors.len().saturating_sub(1)))
+                    .cloned();
+            }
+
+            let pattern = selector.to_lowercase();
+
+            monitors
+                .iter()
+                .find(|e| monitor_connector(e).is_some_and(|e| e.eq_ignore_ascii_case(selector)))
+                .or_else(|| {
+                    monitors.iter().find(|e| {
+                        e.manufacturer()
+                            .is_some_and(|e| e.to_lowercase().contains(&pattern))
+                    })
+                })
+                .cloned()
+        }
+    }
+}
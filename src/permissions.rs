@@ -0,0 +1,49 @@
+use std::fs;
+use std::path::Path;
+
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// everything a widget may do beyond managing its own window and reading shared state
+#[derive(
+    ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capability {
+    Exec,
+    Fs,
+    Clipboard,
+    Notifications,
+    NetworkProxy,
+    NetworkControl,
+    // commands acting on the daemon itself, e.g. kill
+    GlobalCommands,
+    // commands acting on widgets other than the caller
+    OtherWidgets,
+}
+
+impl std::fmt::Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+// `manifest.json` next to the `index.html` of a directory widget
+#[derive(Debug, Default, Deserialize)]
+pub struct WidgetManifest {
+    #[serde(default)]
+    pub permissions: Vec<Capability>,
+}
+
+impl WidgetManifest {
+    pub fn load(directory: &Path) -> Result<Self, String> {
+        let path = directory.join("manifest.json");
+
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(content.as_str())
+                .map_err(|e| format!("invalid manifest {}: {}", path.display(), e)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+}
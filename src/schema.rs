@@ -0,0 +1,318 @@
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::{InstanceType, Schema, SchemaObject, SingleOrVec};
+
+use crate::{
+    bridge::ScriptCommands,
+    monitors::MonitorInfo,
+    services::{
+        compositor::CompositorState, network::NetworkStatus, power::PowerStatus,
+        system::SystemStats,
+    },
+    storage::StorageChange,
+    widget::Widget,
+};
+
+// the api added by www.js, written against the generated types
+const API: &str = include_str!("www.d.ts");
+
+// every type a page posts or receives, the definitions of the types they reach included
+fn generator() -> SchemaGenerator {
+    let mut ret = SchemaSettings::draft07().into_generator();
+
+    ret.subschema_for::<ScriptCommands>();
+    ret.subschema_for::<Widget>();
+    ret.subschema_for::<SystemStats>();
+    ret.subschema_for::<PowerStatus>();
+    ret.subschema_for::<CompositorState>();
+    ret.subschema_for::<NetworkStatus>();
+    ret.subschema_for::<MonitorInfo>();
+    ret.subschema_for::<StorageChange>();
+
+    ret
+}
+
+fn comment(schema: &Schema, indent: &str) -> String {
+    let description = match schema {
+        Schema::Object(SchemaObject {
+            metadata: Some(metadata),
+            ..
+        }) => metadata.description.as_deref(),
+        _ => None,
+    };
+
+    match description {
+        Some(e) => format!("{}/** {} */\n", indent, e.replace('\n', " ")),
+        None => String::new(),
+    }
+}
+
+fn property_name(name: &str) -> String {
+    match name.chars().all(|e| e.is_ascii_alphanumeric() || e == '_') {
+        true => name.to_string(),
+        false => serde_json::to_string(name).unwrap(),
+    }
+}
+
+fn typescript_object(schema: &SchemaObject, indent: &str) -> String {
+    let Some(object) = &schema.object else {
+        return "Record<string, unknown>".to_string();
+    };
+
+    if object.properties.is_empty() {
+        let value = match &object.additional_properties {
+            Some(e) => typescript_type(e, indent),
+            None => "unknown".to_string(),
+        };
+        return format!("Record<string, {}>", value);
+    }
+
+    let inner = format!("{}  ", indent);
+    let mut ret = String::from("{\n");
+    for (name, property) in &object.properties {
+        // `Option` and `#[serde(default)]` fields may be left out
+        let optional = match object.required.contains(name) {
+            true => "",
+            false => "?",
+        };
+
+        ret.push_str(comment(property, &inner).as_str());
+        ret.push_str(
+            format!(
+                "{}{}{}: {};\n",
+                inner,
+                property_name(name),
+                optional,
+                typescript_type(property, &inner)
+            )
+            .as_str(),
+        );
+    }
+    ret.push_str(indent);
+    ret.push('}');
+
+    ret
+}
+
+fn typescript_instance(schema: &SchemaObject, kind: &InstanceType, indent: &str) -> String {
+    match kind {
+        InstanceType::Null => "null".to_string(),
+        InstanceType::Boolean => "boolean".to_string(),
+        InstanceType::Integer | InstanceType::Number => "number".to_string(),
+        InstanceType::String => "string".to_string(),
+        InstanceType::Array => match schema.array.as_ref().and_then(|e| e.items.as_ref()) {
+            Some(SingleOrVec::Single(e)) => format!("Array<{}>", typescript_type(e, indent)),
+            Some(SingleOrVec::Vec(e)) => format!("[{}]", typescript_union(e, indent, ", ")),
+            None => "Array<unknown>".to_string(),
+        },
+        InstanceType::Object => typescript_object(schema, indent),
+    }
+}
+
+fn typescript_union(schemas: &[Schema], indent: &str, separator: &str) -> String {
+    schemas
+        .iter()
+        .map(|e| typescript_type(e, indent))
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+// the members of a top level `oneOf` or `anyOf`, written one per line
+fn union_members(schema: &Schema) -> Option<&Vec<Schema>> {
+    let Schema::Object(SchemaObject {
+        subschemas: Some(subschemas),
+        ..
+    }) = schema
+    else {
+        return None;
+    };
+
+    subschemas.one_of.as_ref().or(subschemas.any_of.as_ref())
+}
+
+fn typescript_type(schema: &Schema, indent: &str) -> String {
+    let schema = match schema {
+        Schema::Bool(true) => return "unknown".to_string(),
+        Schema::Bool(false) => return "never".to_string(),
+        Schema::Object(e) => e,
+    };
+
+    if let Some(reference) = &schema.reference {
+        return reference.trim_start_matches("#/definitions/").to_string();
+    }
+    if let Some(value) = &schema.const_value {
+        return value.to_string();
+    }
+    if let Some(values) = &schema.enum_values {
+        return values
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join(" | ");
+    }
+    if let Some(subschemas) = &schema.subschemas {
+        if let Some(e) = subschemas.one_of.as_ref().or(subschemas.any_of.as_ref()) {
+            return typescript_union(e, indent, " | ");
+        }
+        // a described field wraps its `$ref` in an `allOf`
+        if let Some(e) = &subschemas.all_of {
+            return typescript_union(e, indent, " & ");
+        }
+    }
+
+    match &schema.instance_type {
+        Some(SingleOrVec::Single(e)) => typescript_instance(schema, e, indent),
+        Some(SingleOrVec::Vec(e)) => e
+            .iter()
+            .map(|e| typescript_instance(schema, e, indent))
+            .collect::<Vec<_>>()
+            .join(" | "),
+        None => "unknown".to_string(),
+    }
+}
+
+// typescript definitions of window.www and the messages accepted by `postMessage`
+pub fn typescript_definitions() -> String {
+    let mut ret = String::from("// generated by `wayland-webkit-widget types`, do not edit\n");
+
+    for (name, schema) in generator().definitions() {
+        ret.push('\n');
+        ret.push_str(comment(schema, "").as_str());
+
+        let line = match (schema, union_members(schema)) {
+            (_, Some(members)) => format!(
+                "export type {} ={};\n",
+                name,
+                members
+                    .iter()
+                    .map(|e| format!("\n{}  | {}", comment(e, "  "), typescript_type(e, "  ")))
+                    .collect::<String>()
+            ),
+            (Schema::Object(e), None)
+                if e.object.as_ref().is_some_and(|e| !e.properties.is_empty()) =>
+            {
+                format!("export interface {} {}\n", name, typescript_object(e, ""))
+            }
+            _ => format!("export type {} = {};\n", name, typescript_type(schema, "")),
+        };
+        ret.push_str(line.as_str());
+    }
+
+    ret.push('\n');
+    ret.push_str(API);
+
+    ret
+}
+
+// the same types as `typescript_definitions`, as a json schema of the messages
+pub fn json_schema() -> String {
+    let root = generator().into_root_schema_for::<ScriptCommands>();
+
+    serde_json::to_string_pretty(&root).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declares_window_www() {
+        let ret = typescript_definitions();
+
+        assert!(ret.contains("declare global {\n  interface Window {\n    www: Www;\n  }\n}"));
+        assert!(ret.contains("export interface Www extends Widget {"));
+        assert!(ret.contains("subscribe(callback: (stats: SystemStats) => void): Unsubscribe;"));
+    }
+
+    // the members declared directly in an interface, at the first level of indentation
+    fn members<'a>(definitions: &'a str, interface: &str) -> Vec<&'a str> {
+        let start = definitions.find(interface).unwrap();
+        let body = &definitions[start..];
+
+        body.lines()
+            .skip(1)
+            .take_while(|e| *e != "}")
+            .filter_map(|e| e.strip_prefix("  "))
+            .filter(|e| e.starts_with(|c: char| c.is_ascii_alphabetic()))
+            .map(|e| e.split(|c| matches!(c, '?' | ':' | '(')).next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn www_keeps_the_widget_fields() {
+        let ret = typescript_definitions();
+        let fields = members(&ret, "export interface Widget {");
+        let api = members(&ret, "export interface Www extends Widget {");
+
+        assert!(fields.contains(&"permissions"));
+        assert!(api.contains(&"onDenied"));
+        // `Object.assign(window.www, widget)` would replace them, and `extends` would not compile
+        for member in api {
+            assert!(
+                !fields.contains(&member),
+                "www.{} shadows a widget field",
+                member
+            );
+        }
+    }
+
+    #[test]
+    fn defines_every_referenced_type() {
+        let ret = typescript_definitions();
+
+        for name in [
+            "ScriptCommands",
+            "BridgeCommands",
+            "CliCommands",
+            "Widget",
+            "WidgetMetadata",
+            "WidgetMetadataArgs",
+            "Length",
+            "Capability",
+            "SystemStats",
+            "PowerStatus",
+            "CompositorState",
+            "NetworkStatus",
+            "MonitorInfo",
+            "StorageChange",
+        ] {
+            assert!(
+                ret.contains(format!("export type {} =", name).as_str())
+                    || ret.contains(format!("export interface {} {{", name).as_str()),
+                "{} is not defined",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn writes_messages_as_unions() {
+        let ret = typescript_definitions();
+
+        assert!(ret.contains("export type ScriptCommands =\n  | BridgeCommands\n  | CliCommands;"));
+        assert!(ret.contains("  | {\n    Subscribe: {\n      topic: string;\n    };\n  }"));
+        assert!(ret.contains(
+            "export type WidgetLayer = \"background\" | \"bottom\" | \"top\" | \"overlay\";"
+        ));
+        assert!(ret.contains("export type Length = number | string;"));
+        // `Option` fields are optional and nullable, skipped fields are left out
+        assert!(ret.contains("  name?: string | null;\n"));
+        assert!(!ret.contains("webview"));
+        // messages the daemon sends itself can't be posted by pages
+        assert!(!ret.contains("OpaqueRegion"));
+    }
+
+    #[test]
+    fn describes_messages_as_json_schema() {
+        let ret = serde_json::from_str::<serde_json::Value>(json_schema().as_str()).unwrap();
+
+        assert_eq!(ret["$schema"], "http://json-schema.org/draft-07/schema#");
+        assert_eq!(
+            ret["anyOf"],
+            serde_json::json!([
+                { "$ref": "#/definitions/BridgeCommands" },
+                { "$ref": "#/definitions/CliCommands" },
+            ])
+        );
+        assert!(ret["definitions"]["WidgetMetadataArgs"].is_object());
+    }
+}
@@ -0,0 +1,233 @@
+use std::io;
+use std::path::Path;
+
+use async_std::channel::Sender;
+use async_std::io::{prelude::BufReadExt, BufReader, ReadExt, WriteExt};
+use async_std::os::unix::net::UnixStream;
+use async_std::stream::StreamExt;
+use serde::Deserialize;
+
+use super::{CompositorState, Workspace};
+
+#[derive(Debug, Deserialize)]
+struct HyprlandWorkspace {
+    id: i64,
+    name: String,
+    monitor: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct HyprlandWorkspaceRef {
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct HyprlandMonitor {
+    name: String,
+    focused: bool,
+    #[serde(rename = "activeWorkspace")]
+    active_workspace: HyprlandWorkspaceRef,
+}
+
+// the request socket answers a single command and closes the connection
+async fn request(instance: &Path, command: &str) -> io::Result<String> {
+    let mut stream = UnixStream::connect(instance.join(".socket.sock")).await?;
+    stream.write_all(command.as_bytes()).await?;
+
+    let mut ret = String::new();
+    stream.read_to_string(&mut ret).await?;
+
+    Ok(ret)
+}
+
+fn parse_state(workspaces: &str, monitors: &str, window: &str) -> io::Result<CompositorState> {
+    let workspaces = serde_json::from_str::<Vec<HyprlandWorkspace>>(workspaces)?;
+    let monitors = serde_json::from_str::<Vec<HyprlandMonitor>>(monitors)?;
+    let window = serde_json::from_str::<serde_json::Value>(window)?;
+
+    let focused = monitors.iter().find(|e| e.focused);
+    let mut workspaces = workspaces
+        .into_iter()
+        .map(|e| Workspace {
+            focused: focused.is_some_and(|f| f.active_workspace.id == e.id),
+            visible: monitors.iter().any(|m| m.active_workspace.id == e.id),
+            id: e.id,
+            name: e.name,
+            monitor: e.monitor,
+        })
+        .collect::<Vec<_>>();
+    workspaces.sort_by_key(|e| e.id);
+
+    Ok(CompositorState {
+        compositor: "hyprland".to_string(),
+        active_monitor: focused.map(|e| e.name.to_owned()),
+        focused_window: window["title"].as_str().map(|e| e.to_string()),
+        workspaces,
+    })
+}
+
+pub async fn query(instance: &Path) -> io::Result<CompositorState> {
+    parse_state(
+        &request(instance, "j/workspaces").await?,
+        &request(instance, "j/monitors").await?,
+        &request(instance, "j/activewindow").await?,
+    )
+}
+
+// a numeric id or `name:<name>`, pages pick the name and hyprctl reads `;` and newlines as the
+// end of the dispatch, or relative selectors such as `e+1` from a bare name
+fn workspace_selector(name: &str) -> io::Result<String> {
+    if name.is_empty() || name.contains(';') || name.chars().any(char::is_control) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid workspace name {:?}", name),
+        ));
+    }
+
+    Ok(match name.parse::<i64>() {
+        Ok(id) => id.to_string(),
+        Err(_) => format!("name:{}", name),
+    })
+}
+
+pub async fn switch_workspace(instance: &Path, name: &str) -> io::Result<()> {
+    let command = format!("dispatch workspace {}", workspace_selector(name)?);
+    request(instance, command.as_str()).await?;
+    Ok(())
+}
+
+pub async fn listen(instance: &Path, tx: Sender<()>) -> io::Result<()> {
+    let stream = UnixStream::connect(instance.join(".socket2.sock")).await?;
+    let mut lines = BufReader::new(stream).lines();
+
+    // every line is an `event>>data` notification, the state is queried again anyway
+    while let Some(line) = lines.next().await {
+        line?;
+        if tx.send(()).await.is_err() {
+            return Ok(());
+        }
+    }
+
+    Err(io::ErrorKind::UnexpectedEof.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use async_std::channel;
+    use async_std::os::unix::net::UnixListener;
+    use async_std::task;
+
+    use super::*;
+
+    const WORKSPACES: &str = r#"[
+        { "id": 3, "name": "3", "monitor": "eDP-1", "windows": 0 },
+        { "id": 1, "name": "1", "monitor": "eDP-1", "windows": 2 },
+        { "id": 2, "name": "web", "monitor": "HDMI-A-1", "windows": 1 }
+    ]"#;
+
+    const MONITORS: &str = r#"[
+        { "name": "eDP-1", "focused": false, "activeWorkspace": { "id": 1, "name": "1" } },
+        { "name": "HDMI-A-1", "focused": true, "activeWorkspace": { "id": 2, "name": "web" } }
+    ]"#;
+
+    const WINDOW: &str = r#"{ "class": "firefox", "title": "Mozilla Firefox" }"#;
+
+    #[test]
+    fn parses_workspaces_monitors_and_window() {
+        let state = parse_state(WORKSPACES, MONITORS, WINDOW).unwrap();
+
+        assert_eq!(state.compositor, "hyprland");
+        assert_eq!(state.active_monitor.as_deref(), Some("HDMI-A-1"));
+        assert_eq!(state.focused_window.as_deref(), Some("Mozilla Firefox"));
+        assert_eq!(
+            state
+                .workspaces
+                .iter()
+                .map(|e| (e.id, e.focused, e.visible))
+                .collect::<Vec<_>>(),
+            [(1, false, true), (2, true, true), (3, false, false)]
+        );
+        assert_eq!(state.workspaces[1].monitor, "HDMI-A-1");
+    }
+
+    #[test]
+    fn parses_an_empty_active_window() {
+        // hyprctl answers `{}` when nothing is focused
+        let state = parse_state(WORKSPACES, MONITORS, "{}").unwrap();
+
+        assert_eq!(state.focused_window, None);
+    }
+
+    #[test]
+    fn rejects_malformed_replies() {
+        assert!(parse_state("unknown request", MONITORS, WINDOW).is_err());
+        assert!(parse_state(WORKSPACES, r#"[{ "name": "eDP-1" }]"#, WINDOW).is_err());
+    }
+
+    #[test]
+    fn selects_workspaces_by_id_or_name() {
+        assert_eq!(workspace_selector("2").unwrap(), "2");
+        assert_eq!(workspace_selector("web").unwrap(), "name:web");
+        assert_eq!(workspace_selector("e+1").unwrap(), "name:e+1");
+        assert_eq!(
+            workspace_selector("[[BATCH]]dispatch exec foo").unwrap(),
+            "name:[[BATCH]]dispatch exec foo"
+        );
+        assert!(workspace_selector("").is_err());
+        assert!(workspace_selector("1; dispatch exec foo").is_err());
+        assert!(workspace_selector("1\ndispatch exec foo").is_err());
+    }
+
+    #[test]
+    fn queries_a_fake_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let instance = dir.path().to_path_buf();
+
+        task::block_on(async {
+            let listener = UnixListener::bind(instance.join(".socket.sock"))
+                .await
+                .unwrap();
+            task::spawn(async move {
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    let mut command = [0u8; 64];
+                    let length = stream.read(&mut command).await.unwrap();
+                    let reply = match &command[..length] {
+                        b"j/workspaces" => WORKSPACES,
+                        b"j/monitors" => MONITORS,
+                        b"j/activewindow" => WINDOW,
+                        _ => "unknown request",
+                    };
+                    stream.write_all(reply.as_bytes()).await.unwrap();
+                }
+            });
+
+            let state = query(&instance).await.unwrap();
+            assert_eq!(state, parse_state(WORKSPACES, MONITORS, WINDOW).unwrap());
+        });
+    }
+
+    #[test]
+    fn forwards_events_until_the_socket_closes() {
+        let dir = tempfile::tempdir().unwrap();
+        let instance = dir.path().to_path_buf();
+
+        task::block_on(async {
+            let listener = UnixListener::bind(instance.join(".socket2.sock"))
+                .await
+                .unwrap();
+            task::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                stream
+                    .write_all(b"workspace>>2\nactivewindow>>firefox,Mozilla Firefox\n")
+                    .await
+                    .unwrap();
+            });
+
+            let (tx, rx) = channel::unbounded();
+            let ret = listen(&instance, tx).await;
+
+            assert_eq!(ret.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+            assert_eq!(rx.len(), 2);
+        });
+    }
+}
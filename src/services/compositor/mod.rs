@@ -0,0 +1,160 @@
+mod hyprland;
+mod sway;
+
+use std::cell::RefCell;
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use async_std::channel;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::app_state::AppState;
+
+pub const COMPOSITOR_TOPIC: &str = "compositor";
+
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, JsonSchema, PartialEq)]
+pub struct Workspace {
+    pub id: i64,
+    pub name: String,
+    pub monitor: String,
+    pub focused: bool,
+    pub visible: bool,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema, PartialEq)]
+pub struct CompositorState {
+    pub compositor: String,
+    pub workspaces: Vec<Workspace>,
+    pub focused_window: Option<String>,
+    pub active_monitor: Option<String>,
+}
+
+// each backend provides `query`, `switch_workspace` and `listen` over its own ipc socket
+#[derive(Debug, Clone)]
+pub enum Compositor {
+    Sway(PathBuf),
+    Hyprland(PathBuf),
+}
+
+fn hyprland_instance(signature: &str) -> PathBuf {
+    // hyprland moved its sockets from /tmp to the runtime dir in v0.40
+    let runtime = env::var("XDG_RUNTIME_DIR")
+        .map(|e| Path::new(e.as_str()).join("hypr").join(signature))
+        .ok();
+
+    match runtime {
+        Some(path) if path.exists() => path,
+        _ => Path::new("/tmp/hypr").join(signature),
+    }
+}
+
+impl Compositor {
+    // pick the backend from the environment the daemon was started in
+    pub fn detect() -> Option<Self> {
+        if let Ok(signature) = env::var("HYPRLAND_INSTANCE_SIGNATURE") {
+            return Some(Compositor::Hyprland(hyprland_instance(signature.as_str())));
+        }
+
+        env::var("SWAYSOCK")
+            .or_else(|_| env::var("I3SOCK"))
+            .ok()
+            .map(|e| Compositor::Sway(PathBuf::from(e)))
+    }
+
+    pub async fn query(&self) -> io::Result<CompositorState> {
+        match self {
+            Compositor::Sway(socket) => sway::query(socket).await,
+            Compositor::Hyprland(instance) => hyprland::query(instance).await,
+        }
+    }
+
+    pub async fn switch_workspace(&self, name: &str) -> io::Result<()> {
+        match self {
+            Compositor::Sway(socket) => sway::switch_workspace(socket, name).await,
+            Compositor::Hyprland(instance) => hyprland::switch_workspace(instance, name).await,
+        }
+    }
+
+    // send a notification on every compositor event, fails when the connection closes
+    pub async fn listen(&self, tx: channel::Sender<()>) -> io::Result<()> {
+        match self {
+            Compositor::Sway(socket) => sway::listen(socket, tx).await,
+            Compositor::Hyprland(instance) => hyprland::listen(instance, tx).await,
+        }
+    }
+}
+
+pub fn start_compositor_service(state: Rc<RefCell<AppState>>) {
+    let Some(compositor) = Compositor::detect() else {
+        return;
+    };
+
+    RefCell::borrow_mut(&state).compositor = Some(compositor.clone());
+
+    let (tx, rx) = channel::unbounded();
+    let listener = compositor.clone();
+
+    // the event socket goes away when the compositor reloads or restarts
+    glib::spawn_future_local(async move {
+        let mut delay = RECONNECT_DELAY;
+
+        loop {
+            let connected_at = Instant::now();
+            let Err(e) = listener.listen(tx.clone()).await else {
+                return;
+            };
+            // a connection that lasted starts over from the shortest delay
+            if connected_at.elapsed() > RECONNECT_MAX_DELAY {
+                delay = RECONNECT_DELAY;
+            }
+            eprintln!(
+                "compositor event socket closed: {}, reconnecting in {:?}",
+                e, delay
+            );
+
+            glib::timeout_future(delay).await;
+            delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+
+            // events may have been missed in the meantime
+            if tx.send(()).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    glib::spawn_future_local(async move {
+        let mut previous = None;
+
+        loop {
+            match compositor.query().await {
+                Ok(current) if previous.as_ref() != Some(&current) => {
+                    let mut app_state = RefCell::borrow_mut(&state);
+                    app_state.publish(COMPOSITOR_TOPIC, &current);
+
+                    // widgets on the `focused` monitor follow the focus
+                    let focus_kept = previous
+                        .as_ref()
+                        .is_some_and(|e| e.active_monitor == current.active_monitor);
+                    if !focus_kept {
+                        app_state.place_widgets();
+                    }
+
+                    previous = Some(current);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("failed to query compositor: {}", e),
+            }
+
+            if rx.recv().await.is_err() {
+                break;
+            }
+        }
+    });
+}
@@ -0,0 +1,246 @@
+use std::io;
+use std::path::Path;
+
+use async_std::channel::Sender;
+use async_std::io::{ReadExt, WriteExt};
+use async_std::os::unix::net::UnixStream;
+use serde::Deserialize;
+
+use super::{CompositorState, Workspace};
+
+// i3-compatible ipc, see sway-ipc(7)
+const MAGIC: &[u8] = b"i3-ipc";
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+const GET_TREE: u32 = 4;
+
+#[derive(Debug, Deserialize)]
+struct SwayWorkspace {
+    num: i64,
+    name: String,
+    output: String,
+    focused: bool,
+    visible: bool,
+}
+
+async fn write_message(stream: &mut UnixStream, kind: u32, payload: &str) -> io::Result<()> {
+    let mut message = MAGIC.to_vec();
+    message.extend((payload.len() as u32).to_ne_bytes());
+    message.extend(kind.to_ne_bytes());
+    message.extend(payload.as_bytes());
+
+    stream.write_all(&message).await
+}
+
+async fn read_message(stream: &mut UnixStream) -> io::Result<serde_json::Value> {
+    // magic, payload length and payload type
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header).await?;
+
+    let length = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+    let mut payload = vec![0u8; length as usize];
+    stream.read_exact(&mut payload).await?;
+
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+async fn request(socket: &Path, kind: u32, payload: &str) -> io::Result<serde_json::Value> {
+    let mut stream = UnixStream::connect(socket).await?;
+    write_message(&mut stream, kind, payload).await?;
+    read_message(&mut stream).await
+}
+
+fn focused_title(node: &serde_json::Value) -> Option<String> {
+    if node["focused"].as_bool() == Some(true) && node["type"] != "workspace" {
+        return node["name"].as_str().map(|e| e.to_string());
+    }
+
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|e| node[e].as_array())
+        .flatten()
+        .find_map(focused_title)
+}
+
+fn parse_state(
+    workspaces: serde_json::Value,
+    tree: &serde_json::Value,
+) -> io::Result<CompositorState> {
+    let workspaces = serde_json::from_value::<Vec<SwayWorkspace>>(workspaces)?;
+
+    Ok(CompositorState {
+        compositor: "sway".to_string(),
+        active_monitor: workspaces
+            .iter()
+            .find(|e| e.focused)
+            .map(|e| e.output.to_owned()),
+        focused_window: focused_title(tree),
+        workspaces: workspaces
+            .into_iter()
+            .map(|e| Workspace {
+                id: e.num,
+                name: e.name,
+                monitor: e.output,
+                focused: e.focused,
+                visible: e.visible,
+            })
+            .collect(),
+    })
+}
+
+pub async fn query(socket: &Path) -> io::Result<CompositorState> {
+    let workspaces = request(socket, GET_WORKSPACES, "").await?;
+    let tree = request(socket, GET_TREE, "").await?;
+
+    parse_state(workspaces, &tree)
+}
+
+pub async fn switch_workspace(socket: &Path, name: &str) -> io::Result<()> {
+    let command = format!("workspace {}", serde_json::to_string(name)?);
+    request(socket, RUN_COMMAND, command.as_str()).await?;
+    Ok(())
+}
+
+pub async fn listen(socket: &Path, tx: Sender<()>) -> io::Result<()> {
+    let mut stream = UnixStream::connect(socket).await?;
+    write_message(&mut stream, SUBSCRIBE, r#"["workspace","window","output"]"#).await?;
+
+    // the first message is the reply to the subscription, the rest are events
+    read_message(&mut stream).await?;
+
+    loop {
+        read_message(&mut stream).await?;
+        if tx.send(()).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_std::channel;
+    use async_std::os::unix::net::UnixListener;
+    use async_std::task;
+    use serde_json::json;
+
+    use super::*;
+
+    fn workspaces() -> serde_json::Value {
+        json!([
+            { "num": 2, "name": "2: web", "output": "HDMI-A-1", "focused": false, "visible": true },
+            { "num": 1, "name": "1", "output": "eDP-1", "focused": true, "visible": true },
+            { "num": 3, "name": "3", "output": "eDP-1", "focused": false, "visible": false },
+        ])
+    }
+
+    fn tree() -> serde_json::Value {
+        json!({
+            "type": "root",
+            "name": "root",
+            "focused": false,
+            "nodes": [{
+                "type": "workspace",
+                "name": "1",
+                "focused": false,
+                "nodes": [{ "type": "con", "name": "terminal", "focused": false, "nodes": [] }],
+                "floating_nodes": [{ "type": "floating_con", "name": "editor", "focused": true }],
+            }],
+        })
+    }
+
+    #[test]
+    fn parses_workspaces_and_tree() {
+        let state = parse_state(workspaces(), &tree()).unwrap();
+
+        assert_eq!(state.compositor, "sway");
+        assert_eq!(state.active_monitor.as_deref(), Some("eDP-1"));
+        assert_eq!(state.focused_window.as_deref(), Some("editor"));
+        assert_eq!(
+            state.workspaces.iter().map(|e| e.id).collect::<Vec<_>>(),
+            [2, 1, 3]
+        );
+        assert_eq!(state.workspaces[0].name, "2: web");
+        assert!(state.workspaces[0].visible && !state.workspaces[0].focused);
+    }
+
+    #[test]
+    fn ignores_a_focused_workspace_without_windows() {
+        let tree = json!({
+            "type": "root",
+            "nodes": [{ "type": "workspace", "name": "1", "focused": true, "nodes": [] }],
+        });
+
+        assert_eq!(focused_title(&tree), None);
+    }
+
+    #[test]
+    fn rejects_malformed_replies() {
+        assert!(parse_state(json!({ "error": "nope" }), &tree()).is_err());
+        assert!(parse_state(json!([{ "num": 1 }]), &tree()).is_err());
+    }
+
+    // answers every request by its payload type, subscriptions end when the stream is dropped
+    async fn serve(listener: UnixListener, events: usize) {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut header = [0u8; 14];
+            if stream.read_exact(&mut header).await.is_err() {
+                continue;
+            }
+            assert_eq!(&header[..6], MAGIC);
+            let length = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+            let kind = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+            let mut payload = vec![0u8; length as usize];
+            stream.read_exact(&mut payload).await.unwrap();
+
+            let reply = match kind {
+                GET_WORKSPACES => workspaces(),
+                GET_TREE => tree(),
+                SUBSCRIBE => json!({ "success": true }),
+                _ => unreachable!(),
+            };
+            write_message(&mut stream, kind, &reply.to_string())
+                .await
+                .unwrap();
+
+            if kind == SUBSCRIBE {
+                for _ in 0..events {
+                    write_message(&mut stream, 0x80000000, r#"{"change":"focus"}"#)
+                        .await
+                        .unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn queries_a_fake_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("sway.sock");
+
+        task::block_on(async {
+            let listener = UnixListener::bind(&socket).await.unwrap();
+            task::spawn(serve(listener, 0));
+
+            let state = query(&socket).await.unwrap();
+            assert_eq!(state, parse_state(workspaces(), &tree()).unwrap());
+        });
+    }
+
+    #[test]
+    fn forwards_events_until_the_socket_closes() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("sway.sock");
+
+        task::block_on(async {
+            let listener = UnixListener::bind(&socket).await.unwrap();
+            task::spawn(serve(listener, 2));
+
+            let (tx, rx) = channel::unbounded();
+            let ret = listen(&socket, tx).await;
+
+            assert_eq!(ret.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+            assert_eq!(rx.len(), 2);
+        });
+    }
+}
@@ -1 +1,6 @@
 pub mod application;
+pub mod compositor;
+pub mod monitors;
+pub mod network;
+pub mod power;
+pub mod system;
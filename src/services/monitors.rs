@@ -0,0 +1,94 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gdk::{Display, Monitor};
+use glib::{clone, SignalHandlerId};
+use gtk::prelude::*;
+
+use crate::{app_state::AppState, monitors::describe_monitors};
+
+pub const MONITORS_TOPIC: &str = "monitors";
+
+type MonitorHandlers = Rc<RefCell<Vec<(Monitor, SignalHandlerId)>>>;
+
+fn monitors_changed(state: &Rc<RefCell<AppState>>, display: &Display) {
+    // gdk may emit these while the state is borrowed, e.g. when a widget window gets mapped
+    glib::idle_add_local_once(clone!(@strong state, @strong display => move || {
+        let mut app_state = RefCell::borrow_mut(&state);
+
+        app_state.publish(MONITORS_TOPIC, &describe_monitors(&display));
+        // indices shift when outputs come and go, so resolve every selector again
+        app_state.place_widgets();
+    }));
+}
+
+fn watch_monitor(
+    state: &Rc<RefCell<AppState>>,
+    display: &Display,
+    monitor: &Monitor,
+    handlers: &MonitorHandlers,
+) {
+    let ids = [
+        monitor.connect_geometry_notify(clone!(@strong state, @strong display => move |_| {
+            monitors_changed(&state, &display);
+        })),
+        monitor.connect_workarea_notify(clone!(@strong state, @strong display => move |_| {
+            monitors_changed(&state, &display);
+        })),
+        monitor.connect_scale_factor_notify(clone!(@strong state, @strong display => move |_| {
+            monitors_changed(&state, &display);
+        })),
+    ];
+
+    RefCell::borrow_mut(handlers).extend(ids.map(|e| (monitor.clone(), e)));
+}
+
+fn unwatch_monitor(monitor: &Monitor, handlers: &MonitorHandlers) {
+    let (removed, kept): (Vec<_>, Vec<_>) =
+        handlers.take().into_iter().partition(|(e, _)| e == monitor);
+
+    handlers.replace(kept);
+
+    for (_, id) in removed {
+        monitor.disconnect(id);
+    }
+}
+
+pub fn start_monitor_service(state: Rc<RefCell<AppState>>) {
+    let display = Display::default().expect("failed to get display");
+    let handlers: MonitorHandlers = Default::default();
+
+    RefCell::borrow_mut(&state).publish(MONITORS_TOPIC, &describe_monitors(&display));
+
+    (0..display.n_monitors())
+        .filter_map(|e| display.monitor(e))
+        .for_each(|e| watch_monitor(&state, &display, &e, &handlers));
+
+    let added = display.connect_monitor_added(
+        clone!(@strong state, @strong handlers => move |display, monitor| {
+            watch_monitor(&state, display, monitor, &handlers);
+            monitors_changed(&state, display);
+        }),
+    );
+    let removed = display.connect_monitor_removed(
+        clone!(@strong state, @strong handlers => move |display, monitor| {
+            unwatch_monitor(monitor, &handlers);
+            monitors_changed(&state, display);
+        }),
+    );
+
+    // the handlers hold the state, let it go with the application
+    let application = RefCell::borrow(&state).application.clone();
+    let ids = RefCell::new(Some((added, removed)));
+
+    application.connect_shutdown(move |_| {
+        if let Some((added, removed)) = ids.take() {
+            display.disconnect(added);
+            display.disconnect(removed);
+        }
+
+        for (monitor, id) in handlers.take() {
+            monitor.disconnect(id);
+        }
+    });
+}
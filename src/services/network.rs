@@ -0,0 +1,690 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use async_std::channel::{self, Sender};
+use gio::{BusType, DBusCallFlags, DBusConnection, DBusSignalFlags, SignalSubscriptionId};
+use glib::{ToVariant, Variant, VariantDict};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::app_state::AppState;
+
+pub const NETWORK_TOPIC: &str = "network";
+
+const NM_BUS: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+const NM_INTERFACE: &str = "org.freedesktop.NetworkManager";
+const NM_SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+#[derive(Debug, Serialize, JsonSchema, PartialEq)]
+pub struct NetworkConnection {
+    pub id: String,
+    // wifi, ethernet, vpn or the raw networkmanager connection type
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub ssid: Option<String>,
+    pub strength: Option<u8>,
+    pub addresses: Vec<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema, PartialEq)]
+pub struct NetworkStatus {
+    // none, portal, limited, full or unknown
+    pub connectivity: String,
+    pub wireless_enabled: bool,
+    pub connection: Option<NetworkConnection>,
+}
+
+async fn call(
+    bus: &DBusConnection,
+    path: &str,
+    interface: &str,
+    method: &str,
+    parameters: Option<&Variant>,
+) -> Result<Variant, glib::Error> {
+    bus.call_future(
+        Some(NM_BUS),
+        path,
+        interface,
+        method,
+        parameters,
+        None,
+        DBusCallFlags::NONE,
+        -1,
+    )
+    .await
+}
+
+async fn property(
+    bus: &DBusConnection,
+    path: &str,
+    interface: &str,
+    name: &str,
+) -> Result<Variant, glib::Error> {
+    let reply = call(
+        bus,
+        path,
+        PROPERTIES_INTERFACE,
+        "Get",
+        Some(&(interface, name).to_variant()),
+    )
+    .await?;
+
+    // unwrap the `(v)` reply
+    Ok(reply.child_value(0).as_variant().unwrap_or(reply))
+}
+
+async fn object_path(bus: &DBusConnection, path: &str, interface: &str, name: &str) -> String {
+    property(bus, path, interface, name)
+        .await
+        .ok()
+        .and_then(|e| e.str().map(|e| e.to_string()))
+        .unwrap_or("/".to_string())
+}
+
+async fn addresses(bus: &DBusConnection, path: &str, interface: &str) -> Vec<String> {
+    if path == "/" {
+        return vec![];
+    }
+
+    match property(bus, path, interface, "AddressData").await {
+        Ok(data) => parse_addresses(&data),
+        Err(_) => vec![],
+    }
+}
+
+// aa{sv}, one dictionary with `address` and `prefix` per address
+fn parse_addresses(data: &Variant) -> Vec<String> {
+    data.iter()
+        .filter_map(|e| {
+            let dict = VariantDict::new(Some(&e));
+            let address = dict.lookup::<String>("address").ok()??;
+            let prefix = dict.lookup::<u32>("prefix").ok()??;
+            Some(format!("{}/{}", address, prefix))
+        })
+        .collect()
+}
+
+fn connection_kind(kind: &str) -> String {
+    match kind {
+        "802-11-wireless" => "wifi".to_string(),
+        "802-3-ethernet" => "ethernet".to_string(),
+        "vpn" | "wireguard" => "vpn".to_string(),
+        e => e.to_string(),
+    }
+}
+
+// NMConnectivityState
+fn connectivity(state: Option<u32>) -> &'static str {
+    match state {
+        Some(1) => "none",
+        Some(2) => "portal",
+        Some(3) => "limited",
+        Some(4) => "full",
+        _ => "unknown",
+    }
+}
+
+// a{sa{sv}}, the `connection` group holds the id and uuid
+fn connection_matches(settings: &Variant, name: &str) -> bool {
+    settings
+        .iter()
+        .filter(|e| e.child_value(0).str() == Some("connection"))
+        .any(|e| {
+            let dict = VariantDict::new(Some(&e.child_value(1)));
+            ["id", "uuid"]
+                .iter()
+                .any(|k| dict.lookup::<String>(k).ok().flatten().as_deref() == Some(name))
+        })
+}
+
+async fn read_connection(bus: &DBusConnection, path: &str) -> Option<NetworkConnection> {
+    let interface = "org.freedesktop.NetworkManager.Connection.Active";
+
+    let id = property(bus, path, interface, "Id")
+        .await
+        .ok()?
+        .get::<String>()?;
+    let kind = connection_kind(
+        &property(bus, path, interface, "Type")
+            .await
+            .ok()?
+            .get::<String>()?,
+    );
+
+    // for wifi connections the specific object is the access point
+    let (ssid, strength) = if kind == "wifi" {
+        let access_point = object_path(bus, path, interface, "SpecificObject").await;
+        let interface = "org.freedesktop.NetworkManager.AccessPoint";
+
+        (
+            property(bus, &access_point, interface, "Ssid")
+                .await
+                .ok()
+                .and_then(|e| e.get::<Vec<u8>>())
+                .map(|e| String::from_utf8_lossy(&e).to_string()),
+            property(bus, &access_point, interface, "Strength")
+                .await
+                .ok()
+                .and_then(|e| e.get::<u8>()),
+        )
+    } else {
+        (None, None)
+    };
+
+    let ip4 = object_path(bus, path, interface, "Ip4Config").await;
+    let ip6 = object_path(bus, path, interface, "Ip6Config").await;
+    let mut addresses = addresses(bus, &ip4, "org.freedesktop.NetworkManager.IP4Config").await;
+    addresses.extend(addresses(bus, &ip6, "org.freedesktop.NetworkManager.IP6Config").await);
+
+    Some(NetworkConnection {
+        id,
+        kind,
+        ssid,
+        strength,
+        addresses,
+    })
+}
+
+pub async fn read_network_status(bus: &DBusConnection) -> NetworkStatus {
+    let connectivity = connectivity(
+        property(bus, NM_PATH, NM_INTERFACE, "Connectivity")
+            .await
+            .ok()
+            .and_then(|e| e.get::<u32>()),
+    );
+
+    let primary = object_path(bus, NM_PATH, NM_INTERFACE, "PrimaryConnection").await;
+    let connection = match primary.as_str() {
+        "/" => None,
+        path => read_connection(bus, path).await,
+    };
+
+    NetworkStatus {
+        connectivity: connectivity.to_string(),
+        wireless_enabled: property(bus, NM_PATH, NM_INTERFACE, "WirelessEnabled")
+            .await
+            .ok()
+            .and_then(|e| e.get::<bool>())
+            .unwrap_or(false),
+        connection,
+    }
+}
+
+pub async fn set_wireless_enabled(bus: &DBusConnection, enabled: bool) -> Result<(), glib::Error> {
+    call(
+        bus,
+        NM_PATH,
+        PROPERTIES_INTERFACE,
+        "Set",
+        Some(&(NM_INTERFACE, "WirelessEnabled", enabled.to_variant()).to_variant()),
+    )
+    .await?;
+
+    Ok(())
+}
+
+// activate a saved connection by its id or uuid, networkmanager picks the device
+pub async fn activate_connection(bus: &DBusConnection, name: &str) -> Result<(), glib::Error> {
+    let reply = call(
+        bus,
+        NM_SETTINGS_PATH,
+        "org.freedesktop.NetworkManager.Settings",
+        "ListConnections",
+        None,
+    )
+    .await?;
+
+    for path in reply.child_value(0).iter() {
+        let Some(path) = path.str().map(|e| e.to_string()) else {
+            continue;
+        };
+        let settings = call(
+            bus,
+            &path,
+            "org.freedesktop.NetworkManager.Settings.Connection",
+            "GetSettings",
+            None,
+        )
+        .await?;
+
+        if connection_matches(&settings.child_value(0), name) {
+            let root = glib::variant::ObjectPath::try_from("/").unwrap();
+            let parameters = (
+                glib::variant::ObjectPath::try_from(path).unwrap(),
+                root.to_owned(),
+                root,
+            )
+                .to_variant();
+
+            call(
+                bus,
+                NM_PATH,
+                NM_INTERFACE,
+                "ActivateConnection",
+                Some(&parameters),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+
+    Err(glib::Error::new(
+        gio::IOErrorEnum::NotFound,
+        format!("no saved connection named {}", name).as_str(),
+    ))
+}
+
+// one message per PropertiesChanged of networkmanager or any of its objects
+fn subscribe_changes(bus: &DBusConnection, tx: Sender<()>) -> SignalSubscriptionId {
+    // peer to peer connections have no bus names to filter by
+    let sender = bus.unique_name().map(|_| NM_BUS);
+
+    bus.signal_subscribe(
+        sender,
+        Some(PROPERTIES_INTERFACE),
+        Some("PropertiesChanged"),
+        None,
+        None,
+        DBusSignalFlags::NONE,
+        move |_, _, _, _, _, _| {
+            let _ = tx.send_blocking(());
+        },
+    )
+}
+
+pub fn start_network_service(state: Rc<RefCell<AppState>>) {
+    glib::spawn_future_local(async move {
+        // DBUS_SYSTEM_BUS_ADDRESS points this at a private bus when testing
+        let bus = match gio::bus_get_future(BusType::System).await {
+            Ok(bus) => bus,
+            Err(e) => {
+                eprintln!("failed to connect to the system bus: {}", e);
+                return;
+            }
+        };
+
+        RefCell::borrow_mut(&state).network = Some(bus.clone());
+
+        let (tx, rx) = channel::unbounded();
+        subscribe_changes(&bus, tx);
+
+        let mut previous = None;
+
+        loop {
+            let current = read_network_status(&bus).await;
+            if previous.as_ref() != Some(&current) {
+                RefCell::borrow_mut(&state).publish(NETWORK_TOPIC, &current);
+                previous = Some(current);
+            }
+
+            if rx.recv().await.is_err() {
+                break;
+            }
+
+            // a single change usually emits a burst of signals, query once for all of them
+            while rx.try_recv().is_ok() {}
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::future::Future;
+    use std::os::unix::net::UnixStream;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use gio::prelude::*;
+    use gio::DBusConnectionFlags;
+    use zbus::zvariant::{ObjectPath, OwnedObjectPath, Value};
+
+    use super::*;
+
+    const ACTIVE_PATH: &str = "/org/freedesktop/NetworkManager/ActiveConnection/1";
+    const ACCESS_POINT_PATH: &str = "/org/freedesktop/NetworkManager/AccessPoint/1";
+    const IP4_PATH: &str = "/org/freedesktop/NetworkManager/IP4Config/1";
+
+    fn path(path: &str) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(path).unwrap()
+    }
+
+    // the parts of networkmanager the service reads and calls
+    struct Manager {
+        wireless_enabled: bool,
+        activated: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.NetworkManager")]
+    impl Manager {
+        fn activate_connection(
+            &self,
+            connection: ObjectPath<'_>,
+            _device: ObjectPath<'_>,
+            _specific_object: ObjectPath<'_>,
+        ) -> OwnedObjectPath {
+            self.activated.lock().unwrap().push(connection.to_string());
+            path(ACTIVE_PATH)
+        }
+
+        #[zbus(property)]
+        fn connectivity(&self) -> u32 {
+            4
+        }
+
+        #[zbus(property)]
+        fn primary_connection(&self) -> OwnedObjectPath {
+            path(ACTIVE_PATH)
+        }
+
+        #[zbus(property)]
+        fn wireless_enabled(&self) -> bool {
+            self.wireless_enabled
+        }
+
+        #[zbus(property)]
+        fn set_wireless_enabled(&mut self, enabled: bool) {
+            self.wireless_enabled = enabled;
+        }
+    }
+
+    struct ActiveConnection;
+
+    #[zbus::interface(name = "org.freedesktop.NetworkManager.Connection.Active")]
+    impl ActiveConnection {
+        #[zbus(property, name = "Id")]
+        fn id(&self) -> String {
+            "Home".to_string()
+        }
+
+        #[zbus(property, name = "Type")]
+        fn kind(&self) -> String {
+            "802-11-wireless".to_string()
+        }
+
+        #[zbus(property, name = "SpecificObject")]
+        fn specific_object(&self) -> OwnedObjectPath {
+            path(ACCESS_POINT_PATH)
+        }
+
+        #[zbus(property, name = "Ip4Config")]
+        fn ip4_config(&self) -> OwnedObjectPath {
+            path(IP4_PATH)
+        }
+
+        #[zbus(property, name = "Ip6Config")]
+        fn ip6_config(&self) -> OwnedObjectPath {
+            path("/")
+        }
+    }
+
+    struct AccessPoint;
+
+    #[zbus::interface(name = "org.freedesktop.NetworkManager.AccessPoint")]
+    impl AccessPoint {
+        #[zbus(property, name = "Ssid")]
+        fn ssid(&self) -> Vec<u8> {
+            b"Home".to_vec()
+        }
+
+        #[zbus(property, name = "Strength")]
+        fn strength(&self) -> u8 {
+            72
+        }
+    }
+
+    struct Ip4Config;
+
+    #[zbus::interface(name = "org.freedesktop.NetworkManager.IP4Config")]
+    impl Ip4Config {
+        #[zbus(property, name = "AddressData")]
+        fn address_data(&self) -> Vec<HashMap<String, Value<'static>>> {
+            vec![HashMap::from([
+                ("address".to_string(), Value::from("192.168.1.20")),
+                ("prefix".to_string(), Value::from(24u32)),
+            ])]
+        }
+    }
+
+    struct Settings;
+
+    #[zbus::interface(name = "org.freedesktop.NetworkManager.Settings")]
+    impl Settings {
+        fn list_connections(&self) -> Vec<OwnedObjectPath> {
+            vec![
+                path("/org/freedesktop/NetworkManager/Settings/1"),
+                path("/org/freedesktop/NetworkManager/Settings/2"),
+            ]
+        }
+    }
+
+    struct SavedConnection {
+        id: &'static str,
+        uuid: &'static str,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.NetworkManager.Settings.Connection")]
+    impl SavedConnection {
+        fn get_settings(&self) -> HashMap<String, HashMap<String, Value<'static>>> {
+            HashMap::from([(
+                "connection".to_string(),
+                HashMap::from([
+                    ("id".to_string(), Value::from(self.id)),
+                    ("uuid".to_string(), Value::from(self.uuid)),
+                ]),
+            )])
+        }
+    }
+
+    struct NetworkManager {
+        bus: DBusConnection,
+        activated: Arc<Mutex<Vec<String>>>,
+        // dropping the server closes the connection
+        _server: zbus::Connection,
+    }
+
+    // serve the mock on one end of a socket pair and connect gio to the other one
+    async fn network_manager() -> NetworkManager {
+        let (client, server) = UnixStream::pair().unwrap();
+        let activated = Arc::new(Mutex::new(vec![]));
+        let manager = Manager {
+            wireless_enabled: true,
+            activated: activated.clone(),
+        };
+
+        // the handshake needs both ends, so the server authenticates on its own thread
+        let server = std::thread::spawn(move || {
+            async_std::task::block_on(
+                zbus::connection::Builder::unix_stream(server)
+                    .server(zbus::Guid::generate())?
+                    .p2p()
+                    .serve_at(NM_PATH, manager)?
+                    .serve_at(ACTIVE_PATH, ActiveConnection)?
+                    .serve_at(ACCESS_POINT_PATH, AccessPoint)?
+                    .serve_at(IP4_PATH, Ip4Config)?
+                    .serve_at(NM_SETTINGS_PATH, Settings)?
+                    .serve_at(
+                        "/org/freedesktop/NetworkManager/Settings/1",
+                        SavedConnection {
+                            id: "Home",
+                            uuid: "0b4c4f51-2a6e-4d7b-9c1f-5e6d7a8b9c0d",
+                        },
+                    )?
+                    .serve_at(
+                        "/org/freedesktop/NetworkManager/Settings/2",
+                        SavedConnection {
+                            id: "Office",
+                            uuid: "7e1d2c3b-4a59-4687-9a0b-1c2d3e4f5a6b",
+                        },
+                    )?
+                    .build(),
+            )
+        });
+
+        let socket = unsafe { gio::Socket::from_fd(client) }.unwrap();
+        let bus = DBusConnection::new_future(
+            &socket.connection_factory_create_connection(),
+            None,
+            DBusConnectionFlags::AUTHENTICATION_CLIENT,
+            None,
+        )
+        .await
+        .unwrap();
+
+        NetworkManager {
+            bus,
+            activated,
+            _server: server.join().unwrap().unwrap(),
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let context = glib::MainContext::new();
+        context
+            .with_thread_default(|| context.block_on(future))
+            .unwrap()
+    }
+
+    fn address(address: &str, prefix: u32) -> Variant {
+        let dict = VariantDict::new(None);
+        dict.insert("address", address);
+        dict.insert("prefix", prefix);
+        dict.end()
+    }
+
+    fn settings(group: &str, id: &str, uuid: &str) -> Variant {
+        let connection = HashMap::from([
+            ("id".to_string(), id.to_variant()),
+            ("uuid".to_string(), uuid.to_variant()),
+        ]);
+        HashMap::from([(group.to_string(), connection)]).to_variant()
+    }
+
+    #[test]
+    fn parses_address_data() {
+        let data = Variant::array_from_iter_with_type(
+            glib::VariantTy::VARDICT,
+            [address("192.168.1.20", 24), address("fe80::1", 64)],
+        );
+
+        assert_eq!(parse_addresses(&data), ["192.168.1.20/24", "fe80::1/64"]);
+    }
+
+    #[test]
+    fn skips_incomplete_addresses() {
+        let dict = VariantDict::new(None);
+        dict.insert("address", "10.0.0.2");
+        let data = Variant::array_from_iter_with_type(
+            glib::VariantTy::VARDICT,
+            [dict.end(), address("10.0.0.3", 8)],
+        );
+
+        assert_eq!(parse_addresses(&data), ["10.0.0.3/8"]);
+    }
+
+    #[test]
+    fn maps_connection_types_and_connectivity() {
+        assert_eq!(connection_kind("802-11-wireless"), "wifi");
+        assert_eq!(connection_kind("802-3-ethernet"), "ethernet");
+        assert_eq!(connection_kind("wireguard"), "vpn");
+        assert_eq!(connection_kind("bluetooth"), "bluetooth");
+
+        assert_eq!(connectivity(Some(4)), "full");
+        assert_eq!(connectivity(Some(2)), "portal");
+        assert_eq!(connectivity(Some(0)), "unknown");
+        assert_eq!(connectivity(None), "unknown");
+    }
+
+    #[test]
+    fn matches_saved_connections_by_id_or_uuid() {
+        let home = settings("connection", "Home", "0b4c4f51-2a6e-4d7b-9c1f-5e6d7a8b9c0d");
+
+        assert!(connection_matches(&home, "Home"));
+        assert!(connection_matches(
+            &home,
+            "0b4c4f51-2a6e-4d7b-9c1f-5e6d7a8b9c0d"
+        ));
+        assert!(!connection_matches(&home, "home"));
+        assert!(!connection_matches(
+            &settings("802-11-wireless", "Home", ""),
+            "Home"
+        ));
+    }
+
+    #[test]
+    fn reads_the_network_status() {
+        block_on(async {
+            let nm = network_manager().await;
+
+            assert_eq!(
+                read_network_status(&nm.bus).await,
+                NetworkStatus {
+                    connectivity: "full".to_string(),
+                    wireless_enabled: true,
+                    connection: Some(NetworkConnection {
+                        id: "Home".to_string(),
+                        kind: "wifi".to_string(),
+                        ssid: Some("Home".to_string()),
+                        strength: Some(72),
+                        addresses: vec!["192.168.1.20/24".to_string()],
+                    }),
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn toggles_wireless() {
+        block_on(async {
+            let nm = network_manager().await;
+
+            set_wireless_enabled(&nm.bus, false).await.unwrap();
+            assert!(!read_network_status(&nm.bus).await.wireless_enabled);
+
+            set_wireless_enabled(&nm.bus, true).await.unwrap();
+            assert!(read_network_status(&nm.bus).await.wireless_enabled);
+        });
+    }
+
+    #[test]
+    fn activates_saved_connections_by_id_or_uuid() {
+        block_on(async {
+            let nm = network_manager().await;
+
+            activate_connection(&nm.bus, "Office").await.unwrap();
+            activate_connection(&nm.bus, "0b4c4f51-2a6e-4d7b-9c1f-5e6d7a8b9c0d")
+                .await
+                .unwrap();
+
+            assert_eq!(
+                *nm.activated.lock().unwrap(),
+                [
+                    "/org/freedesktop/NetworkManager/Settings/2",
+                    "/org/freedesktop/NetworkManager/Settings/1"
+                ]
+            );
+
+            let error = activate_connection(&nm.bus, "Cafe").await.unwrap_err();
+            assert!(error.matches(gio::IOErrorEnum::NotFound));
+            assert_eq!(nm.activated.lock().unwrap().len(), 2);
+        });
+    }
+
+    #[test]
+    fn notifies_property_changes() {
+        block_on(async {
+            let nm = network_manager().await;
+            let (tx, rx) = channel::unbounded();
+            subscribe_changes(&nm.bus, tx);
+
+            set_wireless_enabled(&nm.bus, false).await.unwrap();
+
+            async_std::future::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("no PropertiesChanged signal")
+                .unwrap();
+        });
+    }
+}
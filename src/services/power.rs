@@ -0,0 +1,241 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::app_state::AppState;
+
+pub const POWER_TOPIC: &str = "power";
+
+#[derive(Debug, Serialize, JsonSchema, PartialEq)]
+pub struct BatteryStatus {
+    pub name: String,
+    pub percentage: f64,
+    // charging, discharging, full, not charging or unknown
+    pub state: String,
+    // seconds, only known while discharging or charging
+    pub time_to_empty: Option<u64>,
+    pub time_to_full: Option<u64>,
+}
+
+#[derive(Debug, Serialize, JsonSchema, PartialEq)]
+pub struct PowerStatus {
+    pub ac_online: bool,
+    pub batteries: Vec<BatteryStatus>,
+}
+
+fn read_value(supply: &Path, name: &str) -> Option<String> {
+    fs::read_to_string(supply.join(name))
+        .ok()
+        .map(|e| e.trim().to_string())
+}
+
+fn read_number(supply: &Path, name: &str) -> Option<f64> {
+    read_value(supply, name)?.parse::<f64>().ok()
+}
+
+fn read_battery(supply: &Path) -> BatteryStatus {
+    let state = read_value(supply, "status")
+        .unwrap_or("unknown".to_string())
+        .to_lowercase();
+
+    // batteries report either energy (µWh, µW) or charge (µAh, µA)
+    let (now, full, rate) = match read_number(supply, "energy_now") {
+        Some(now) => (
+            Some(now),
+            read_number(supply, "energy_full"),
+            read_number(supply, "power_now"),
+        ),
+        None => (
+            read_number(supply, "charge_now"),
+            read_number(supply, "charge_full"),
+            read_number(supply, "current_now"),
+        ),
+    };
+
+    let percentage = match (read_number(supply, "capacity"), now, full) {
+        (Some(capacity), _, _) => capacity,
+        (None, Some(now), Some(full)) if full > 0.0 => now * 100.0 / full,
+        _ => 0.0,
+    };
+
+    let seconds = |amount: f64| match rate {
+        Some(rate) if rate > 0.0 => Some((amount / rate * 3600.0) as u64),
+        _ => None,
+    };
+
+    BatteryStatus {
+        name: supply
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        percentage,
+        time_to_empty: match (state.as_str(), now) {
+            ("discharging", Some(now)) => seconds(now),
+            _ => None,
+        },
+        time_to_full: match (state.as_str(), now, full) {
+            ("charging", Some(now), Some(full)) => seconds((full - now).max(0.0)),
+            _ => None,
+        },
+        state,
+    }
+}
+
+pub fn read_power_status(root: &Path) -> PowerStatus {
+    let mut ret = PowerStatus {
+        ac_online: false,
+        batteries: vec![],
+    };
+
+    let Ok(supplies) = fs::read_dir(root.join("sys/class/power_supply")) else {
+        return ret;
+    };
+
+    let mut supplies = supplies
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect::<Vec<_>>();
+    supplies.sort();
+
+    for supply in supplies {
+        match read_value(&supply, "type").as_deref() {
+            Some("Battery") => ret.batteries.push(read_battery(&supply)),
+            Some("Mains") | Some("USB") => {
+                ret.ac_online |= read_value(&supply, "online").as_deref() == Some("1");
+            }
+            _ => {}
+        }
+    }
+
+    ret
+}
+
+pub fn start_power_service(state: Rc<RefCell<AppState>>, root: PathBuf, interval: u64) {
+    let status = read_power_status(&root);
+    RefCell::borrow_mut(&state).publish(POWER_TOPIC, &status);
+    let mut previous = Some(status);
+
+    glib::timeout_add_local(Duration::from_millis(interval), move || {
+        let Ok(mut app_state) = state.try_borrow_mut() else {
+            return glib::ControlFlow::Continue;
+        };
+
+        // sysfs has no change notifications for most drivers, so only publish differences
+        let status = read_power_status(&root);
+        if previous.as_ref() != Some(&status) {
+            app_state.publish(POWER_TOPIC, &status);
+            previous = Some(status);
+        }
+
+        glib::ControlFlow::Continue
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supply(root: &Path, name: &str, values: &[(&str, &str)]) {
+        let path = root.join("sys/class/power_supply").join(name);
+        fs::create_dir_all(&path).unwrap();
+        values
+            .iter()
+            .for_each(|(k, v)| fs::write(path.join(k), format!("{}\n", v)).unwrap());
+    }
+
+    #[test]
+    fn reads_an_empty_root() {
+        let root = tempfile::tempdir().unwrap();
+
+        assert_eq!(
+            read_power_status(root.path()),
+            PowerStatus {
+                ac_online: false,
+                batteries: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn reads_batteries_and_mains() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        supply(root, "AC", &[("type", "Mains"), ("online", "1")]);
+        // energy based, 20Wh of 40Wh left at 10W
+        supply(
+            root,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("energy_now", "20000000"),
+                ("energy_full", "40000000"),
+                ("power_now", "10000000"),
+            ],
+        );
+        // charge based, 1Ah missing at 2A, capacity wins over the computed percentage
+        supply(
+            root,
+            "BAT1",
+            &[
+                ("type", "Battery"),
+                ("status", "Charging"),
+                ("capacity", "75"),
+                ("charge_now", "3000000"),
+                ("charge_full", "4000000"),
+                ("current_now", "2000000"),
+            ],
+        );
+
+        let status = read_power_status(root);
+
+        assert!(status.ac_online);
+        assert_eq!(
+            status.batteries,
+            vec![
+                BatteryStatus {
+                    name: "BAT0".to_string(),
+                    percentage: 50.0,
+                    state: "discharging".to_string(),
+                    time_to_empty: Some(7200),
+                    time_to_full: None,
+                },
+                BatteryStatus {
+                    name: "BAT1".to_string(),
+                    percentage: 75.0,
+                    state: "charging".to_string(),
+                    time_to_empty: None,
+                    time_to_full: Some(1800),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_offline_usb_and_unknown_rates() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        supply(root, "usb", &[("type", "USB"), ("online", "0")]);
+        supply(
+            root,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("energy_now", "1"),
+            ],
+        );
+
+        let status = read_power_status(root);
+
+        assert!(!status.ac_online);
+        assert_eq!(status.batteries[0].percentage, 0.0);
+        assert_eq!(status.batteries[0].time_to_empty, None);
+    }
+}
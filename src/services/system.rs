@@ -0,0 +1,473 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::app_state::AppState;
+
+pub const SYSTEM_TOPIC: &str = "sys";
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CpuStats {
+    // usage in percent across all cores
+    pub total: f64,
+    // usage in percent of each core
+    pub cores: Vec<f64>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct MemoryStats {
+    pub total: u64,
+    pub available: u64,
+    pub used: u64,
+    pub swap_total: u64,
+    pub swap_used: u64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct NetworkStats {
+    pub interface: String,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    // bytes per second since the previous sample
+    pub rx_rate: f64,
+    pub tx_rate: f64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DiskStats {
+    pub device: String,
+    pub mount_point: String,
+    pub total: u64,
+    pub available: u64,
+    pub used: u64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TemperatureStats {
+    pub chip: String,
+    pub label: String,
+    pub celsius: f64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SystemStats {
+    pub cpu: CpuStats,
+    pub memory: MemoryStats,
+    pub network: Vec<NetworkStats>,
+    pub disks: Vec<DiskStats>,
+    pub temperatures: Vec<TemperatureStats>,
+}
+
+// cumulative counters of a cpu line in /proc/stat
+#[derive(Debug, Clone, Copy, Default)]
+struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+// procfs only exposes cumulative counters, so rates are computed against the previous sample
+pub struct SystemSampler {
+    root: PathBuf,
+    cpu: Vec<CpuTimes>,
+    network: HashMap<String, (u64, u64)>,
+    sampled_at: Option<Instant>,
+}
+
+fn read_file(root: &Path, path: &str) -> String {
+    fs::read_to_string(root.join(path)).unwrap_or_default()
+}
+
+fn usage(previous: Option<&CpuTimes>, current: &CpuTimes) -> f64 {
+    let previous = previous.copied().unwrap_or_default();
+    let total = current.total.saturating_sub(previous.total);
+
+    if total == 0 {
+        return 0.0;
+    }
+
+    current.busy.saturating_sub(previous.busy) as f64 * 100.0 / total as f64
+}
+
+fn parse_cpu_times(stat: &str) -> Vec<CpuTimes> {
+    // the aggregated "cpu" line comes first, followed by one "cpuN" line per core
+    stat.lines()
+        .filter(|l| l.starts_with("cpu"))
+        .map(|l| {
+            let fields = l
+                .split_whitespace()
+                .skip(1)
+                .take(8)
+                .map(|e| e.parse::<u64>().unwrap_or(0))
+                .collect::<Vec<_>>();
+            let total = fields.iter().sum::<u64>();
+            // idle + iowait
+            let idle = fields.get(3).unwrap_or(&0) + fields.get(4).unwrap_or(&0);
+
+            CpuTimes {
+                busy: total.saturating_sub(idle),
+                total,
+            }
+        })
+        .collect()
+}
+
+fn parse_memory(meminfo: &str) -> MemoryStats {
+    let values = meminfo
+        .lines()
+        .filter_map(|l| {
+            let (key, value) = l.split_once(':')?;
+            let kb = value.split_whitespace().next()?.parse::<u64>().ok()?;
+            Some((key.trim(), kb * 1024))
+        })
+        .collect::<HashMap<_, _>>();
+    let get = |key: &str| values.get(key).copied().unwrap_or(0);
+
+    MemoryStats {
+        total: get("MemTotal"),
+        available: get("MemAvailable"),
+        used: get("MemTotal").saturating_sub(get("MemAvailable")),
+        swap_total: get("SwapTotal"),
+        swap_used: get("SwapTotal").saturating_sub(get("SwapFree")),
+    }
+}
+
+fn parse_network(dev: &str) -> Vec<(String, u64, u64)> {
+    // the first two lines are headers
+    dev.lines()
+        .skip(2)
+        .filter_map(|l| {
+            let (interface, counters) = l.split_once(':')?;
+            let counters = counters
+                .split_whitespace()
+                .map(|e| e.parse::<u64>().unwrap_or(0))
+                .collect::<Vec<_>>();
+
+            Some((
+                interface.trim().to_string(),
+                *counters.first()?,
+                *counters.get(8)?,
+            ))
+        })
+        .collect()
+}
+
+fn statvfs(path: &Path) -> Option<(u64, u64, u64)> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+
+    let block_size = stat.f_frsize as u64;
+
+    Some((
+        stat.f_blocks as u64 * block_size,
+        stat.f_bavail as u64 * block_size,
+        (stat.f_blocks as u64 - stat.f_bfree as u64) * block_size,
+    ))
+}
+
+fn read_disks(root: &Path) -> Vec<DiskStats> {
+    read_file(root, "proc/mounts")
+        .lines()
+        .filter_map(|l| {
+            let mut fields = l.split_whitespace();
+            let device = fields.next()?;
+            let mount_point = fields.next()?;
+
+            // only report block devices, skip pseudo filesystems
+            if !device.starts_with("/dev/") {
+                return None;
+            }
+
+            let (total, available, used) =
+                statvfs(&root.join(mount_point.trim_start_matches('/')))?;
+
+            Some(DiskStats {
+                device: device.to_string(),
+                mount_point: mount_point.to_string(),
+                total,
+                available,
+                used,
+            })
+        })
+        .collect()
+}
+
+fn read_temperatures(root: &Path) -> Vec<TemperatureStats> {
+    let mut ret = vec![];
+
+    let Ok(chips) = fs::read_dir(root.join("sys/class/hwmon")) else {
+        return ret;
+    };
+
+    let mut chips = chips
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect::<Vec<_>>();
+    chips.sort();
+
+    for chip in chips {
+        let name = fs::read_to_string(chip.join("name")).unwrap_or_default();
+        let Ok(entries) = fs::read_dir(&chip) else {
+            continue;
+        };
+
+        let mut inputs = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|e| e.starts_with("temp") && e.ends_with("_input"))
+            .collect::<Vec<_>>();
+        inputs.sort();
+
+        for input in inputs {
+            let Some(millidegrees) = fs::read_to_string(chip.join(&input))
+                .ok()
+                .and_then(|e| e.trim().parse::<f64>().ok())
+            else {
+                continue;
+            };
+            let sensor = input.trim_end_matches("_input");
+            let label = fs::read_to_string(chip.join(format!("{}_label", sensor)))
+                .unwrap_or(sensor.to_string());
+
+            ret.push(TemperatureStats {
+                chip: name.trim().to_string(),
+                label: label.trim().to_string(),
+                celsius: millidegrees / 1000.0,
+            });
+        }
+    }
+
+    ret
+}
+
+impl SystemSampler {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            cpu: vec![],
+            network: HashMap::new(),
+            sampled_at: None,
+        }
+    }
+
+    pub fn sample(&mut self) -> SystemStats {
+        let now = Instant::now();
+        let elapsed = self
+            .sampled_at
+            .map(|e| now.duration_since(e).as_secs_f64())
+            .unwrap_or(0.0);
+
+        let cpu = parse_cpu_times(&read_file(&self.root, "proc/stat"));
+        let usages = cpu
+            .iter()
+            .enumerate()
+            .map(|(i, e)| usage(self.cpu.get(i), e))
+            .collect::<Vec<_>>();
+
+        let network = parse_network(&read_file(&self.root, "proc/net/dev"))
+            .into_iter()
+            .map(|(interface, rx_bytes, tx_bytes)| {
+                let rate = |current: u64, previous: u64| {
+                    if elapsed > 0.0 {
+                        current.saturating_sub(previous) as f64 / elapsed
+                    } else {
+                        0.0
+                    }
+                };
+                let (rx_rate, tx_rate) = match self.network.get(&interface) {
+                    Some((rx, tx)) => (rate(rx_bytes, *rx), rate(tx_bytes, *tx)),
+                    None => (0.0, 0.0),
+                };
+
+                NetworkStats {
+                    interface,
+                    rx_bytes,
+                    tx_bytes,
+                    rx_rate,
+                    tx_rate,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        self.cpu = cpu;
+        self.network = network
+            .iter()
+            .map(|e| (e.interface.to_owned(), (e.rx_bytes, e.tx_bytes)))
+            .collect();
+        self.sampled_at = Some(now);
+
+        SystemStats {
+            cpu: CpuStats {
+                total: usages.first().copied().unwrap_or(0.0),
+                cores: usages.into_iter().skip(1).collect(),
+            },
+            memory: parse_memory(&read_file(&self.root, "proc/meminfo")),
+            network,
+            disks: read_disks(&self.root),
+            temperatures: read_temperatures(&self.root),
+        }
+    }
+}
+
+pub fn start_system_service(state: Rc<RefCell<AppState>>, root: PathBuf, interval: u64) {
+    let mut sampler = SystemSampler::new(root);
+
+    glib::timeout_add_local(Duration::from_millis(interval), move || {
+        // the state may be borrowed by a pending socket command, try again on the next tick
+        let Ok(app_state) = state.try_borrow() else {
+            return glib::ControlFlow::Continue;
+        };
+
+        // avoid touching procfs while nobody is listening
+        if app_state.has_subscribers(SYSTEM_TOPIC) {
+            app_state.emit(SYSTEM_TOPIC, &sampler.sample());
+        }
+
+        glib::ControlFlow::Continue
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAT: &str = "\
+cpu  100 0 50 800 50 0 0 0 0 0
+cpu0 60 0 20 400 20 0 0 0 0 0
+cpu1 40 0 30 400 30 0 0 0 0 0
+intr 12345 0 0
+ctxt 67890
+";
+
+    const MEMINFO: &str = "\
+MemTotal:       16000 kB
+MemFree:         2000 kB
+MemAvailable:    4000 kB
+SwapTotal:       2000 kB
+SwapFree:         500 kB
+";
+
+    const NET_DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:     500       5    0    0    0     0          0         0      500       5    0    0    0     0       0          0
+  eth0:    1000      10    0    0    0     0          0         0     2000      20    0    0    0     0       0          0
+";
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn parses_cpu_times() {
+        let times = parse_cpu_times(STAT);
+
+        assert_eq!(times.len(), 3);
+        assert_eq!((times[0].busy, times[0].total), (150, 1000));
+        assert_eq!((times[1].busy, times[1].total), (80, 500));
+        assert_eq!((times[2].busy, times[2].total), (70, 500));
+    }
+
+    #[test]
+    fn computes_usage_against_the_previous_sample() {
+        let previous = CpuTimes {
+            busy: 100,
+            total: 1000,
+        };
+        let current = CpuTimes {
+            busy: 150,
+            total: 1100,
+        };
+
+        assert_eq!(usage(None, &previous), 10.0);
+        assert_eq!(usage(Some(&previous), &current), 50.0);
+        assert_eq!(usage(Some(&current), &current), 0.0);
+    }
+
+    #[test]
+    fn parses_memory() {
+        let memory = parse_memory(MEMINFO);
+
+        assert_eq!(memory.total, 16000 * 1024);
+        assert_eq!(memory.available, 4000 * 1024);
+        assert_eq!(memory.used, 12000 * 1024);
+        assert_eq!(memory.swap_total, 2000 * 1024);
+        assert_eq!(memory.swap_used, 1500 * 1024);
+    }
+
+    #[test]
+    fn parses_network() {
+        assert_eq!(
+            parse_network(NET_DEV),
+            vec![
+                ("lo".to_string(), 500, 500),
+                ("eth0".to_string(), 1000, 2000),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_malformed_input() {
+        assert!(parse_cpu_times("").is_empty());
+        assert_eq!(parse_memory("garbage").total, 0);
+        assert!(parse_network("header\nheader\nno counters here").is_empty());
+    }
+
+    #[test]
+    fn samples_a_fake_root() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        write(root, "proc/stat", STAT);
+        write(root, "proc/meminfo", MEMINFO);
+        write(root, "proc/net/dev", NET_DEV);
+        write(root, "proc/mounts", "proc /proc proc rw 0 0\n");
+        write(root, "sys/class/hwmon/hwmon0/name", "coretemp\n");
+        write(root, "sys/class/hwmon/hwmon0/temp1_input", "45500\n");
+        write(root, "sys/class/hwmon/hwmon0/temp1_label", "Package id 0\n");
+        write(root, "sys/class/hwmon/hwmon0/temp2_input", "40000\n");
+
+        let mut sampler = SystemSampler::new(root.to_path_buf());
+        let first = sampler.sample();
+
+        assert_eq!(first.cpu.total, 15.0);
+        assert_eq!(first.cpu.cores, vec![16.0, 14.0]);
+        assert_eq!(first.memory.used, 12000 * 1024);
+        assert!(first.network.iter().all(|e| e.rx_rate == 0.0));
+        // pseudo filesystems are skipped
+        assert!(first.disks.is_empty());
+        assert_eq!(first.temperatures.len(), 2);
+        assert_eq!(first.temperatures[0].chip, "coretemp");
+        assert_eq!(first.temperatures[0].label, "Package id 0");
+        assert_eq!(first.temperatures[0].celsius, 45.5);
+        assert_eq!(first.temperatures[1].label, "temp2");
+
+        write(root, "proc/stat", &STAT.replacen("cpu  100", "cpu  200", 1));
+        write(root, "proc/net/dev", &NET_DEV.replacen("1000", "3000", 1));
+        std::thread::sleep(Duration::from_millis(10));
+        let second = sampler.sample();
+
+        // 100 more busy jiffies out of 100 more in total
+        assert_eq!(second.cpu.total, 100.0);
+        let eth0 = second
+            .network
+            .iter()
+            .find(|e| e.interface == "eth0")
+            .unwrap();
+        assert_eq!(eth0.rx_bytes, 3000);
+        assert!(eth0.rx_rate > 0.0);
+        assert_eq!(eth0.tx_rate, 0.0);
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+pub const GLOBAL_NAMESPACE: &str = "global";
+
+// a key value store persisted as a single json file, split into namespaces
+#[derive(Debug)]
+pub struct Storage {
+    path: PathBuf,
+    namespaces: HashMap<String, HashMap<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct StorageChange<'a> {
+    pub namespace: &'a str,
+    pub key: &'a str,
+    // null when the key was removed
+    pub value: &'a serde_json::Value,
+}
+
+// storage.json -> storage.json.<suffix>
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut ret = path.as_os_str().to_owned();
+    ret.push(".");
+    ret.push(suffix);
+    ret.into()
+}
+
+impl Storage {
+    pub fn load(path: PathBuf) -> Self {
+        let namespaces = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(content.as_str()).unwrap_or_else(|e| {
+                // keep the broken file around instead of overwriting it on the next save
+                let corrupt = suffixed(&path, "corrupt");
+                eprintln!(
+                    "failed to parse {}: {}, moving it to {}",
+                    path.display(),
+                    e,
+                    corrupt.display()
+                );
+                if let Err(e) = fs::rename(&path, &corrupt) {
+                    eprintln!("failed to move {}: {}", path.display(), e);
+                }
+                HashMap::default()
+            }),
+            Err(e) => {
+                if e.kind() != io::ErrorKind::NotFound {
+                    eprintln!("failed to read {}: {}", path.display(), e);
+                }
+                HashMap::default()
+            }
+        };
+
+        Self { path, namespaces }
+    }
+
+    // write a sibling file and rename it over the old one so a crash never leaves half a file
+    fn save(&self) {
+        if let Some(parent) = self.path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+
+        let content = serde_json::to_string_pretty(&self.namespaces).unwrap();
+        let temporary = suffixed(&self.path, "tmp");
+        if let Err(e) =
+            fs::write(&temporary, content).and_then(|_| fs::rename(&temporary, &self.path))
+        {
+            eprintln!("failed to write {}: {}", self.path.display(), e);
+            let _ = fs::remove_file(&temporary);
+        }
+    }
+
+    pub fn get(&self, namespace: &str, key: &str) -> serde_json::Value {
+        self.namespaces
+            .get(namespace)
+            .and_then(|e| e.get(key))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn entries(&self, namespace: &str) -> serde_json::Value {
+        serde_json::to_value(self.namespaces.get(namespace).cloned().unwrap_or_default()).unwrap()
+    }
+
+    // setting null removes the key
+    pub fn set(&mut self, namespace: &str, key: &str, value: serde_json::Value) {
+        let entries = self.namespaces.entry(namespace.to_owned()).or_default();
+
+        if value.is_null() {
+            entries.remove(key);
+        } else {
+            entries.insert(key.to_owned(), value);
+        }
+
+        self.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn persists_entries_across_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("www").join("storage.json");
+
+        let mut storage = Storage::load(path.clone());
+        storage.set(GLOBAL_NAMESPACE, "theme", json!("dark"));
+        storage.set("clock", "format", json!({ "seconds": true }));
+        storage.set("clock", "removed", json!(1));
+        storage.set("clock", "removed", serde_json::Value::Null);
+
+        let storage = Storage::load(path.clone());
+        assert_eq!(storage.get(GLOBAL_NAMESPACE, "theme"), json!("dark"));
+        assert_eq!(
+            storage.entries("clock"),
+            json!({ "format": { "seconds": true } })
+        );
+        assert_eq!(storage.get("clock", "missing"), serde_json::Value::Null);
+        assert!(!suffixed(&path, "tmp").exists());
+    }
+
+    #[test]
+    fn moves_a_corrupt_file_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("storage.json");
+        fs::write(&path, "{ \"global\": ").unwrap();
+
+        let mut storage = Storage::load(path.clone());
+        assert_eq!(storage.entries(GLOBAL_NAMESPACE), json!({}));
+        assert_eq!(
+            fs::read_to_string(suffixed(&path, "corrupt")).unwrap(),
+            "{ \"global\": "
+        );
+
+        storage.set(GLOBAL_NAMESPACE, "theme", json!("light"));
+        assert_eq!(
+            Storage::load(path).get(GLOBAL_NAMESPACE, "theme"),
+            json!("light")
+        );
+    }
+}
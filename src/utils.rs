@@ -1,7 +1,8 @@
-use crate::cli::QueryArgs;
+use crate::cli::{MatchMode, QueryArgs};
 use crate::widget::Widget;
 use async_std::io::{prelude::BufReadExt, BufReader, WriteExt};
 use async_std::os::unix::net::UnixStream;
+use regex::Regex;
 use std::env;
 use std::path::{Path, PathBuf};
 
@@ -47,16 +48,264 @@ pub fn get_widget_dir_path() -> PathBuf {
     Path::new(xdg_config_path.as_str()).join("www")
 }
 
-pub fn widget_filter(widget: &Widget, query: &QueryArgs) -> bool {
-    (query.id.is_none() || query.id.as_ref().is_some_and(|e| widget.id.contains(e)))
-        && (query.url.is_none()
-            || query
-                .url
-                .as_ref()
-                .is_some_and(|e| widget.url.contains(e)))
-        && (query.tags.is_none()
-            || query
-                .tags
-                .as_ref()
-                .is_some_and(|e| widget.tags.iter().any(|t| e.contains(t))))
+pub fn get_state_dir_path() -> PathBuf {
+    let default_state_path = format!("{}/.local/state", std::env::var("HOME").unwrap());
+    let xdg_state_path = env::var("XDG_STATE_HOME").unwrap_or(default_state_path);
+
+    Path::new(xdg_state_path.as_str()).join("www")
+}
+
+// the parts of `new` which differ from `old`, removed keys are reported as null
+pub fn json_diff(old: &serde_json::Value, new: &serde_json::Value) -> Option<serde_json::Value> {
+    if old == new {
+        return None;
+    }
+
+    match (old, new) {
+        (serde_json::Value::Object(old), serde_json::Value::Object(new)) => {
+            let mut ret = serde_json::Map::new();
+
+            new.iter().for_each(|(k, v)| {
+                if let Some(d) = json_diff(old.get(k).unwrap_or(&serde_json::Value::Null), v) {
+                    ret.insert(k.to_owned(), d);
+                }
+            });
+            old.keys().filter(|k| !new.contains_key(*k)).for_each(|k| {
+                ret.insert(k.to_owned(), serde_json::Value::Null);
+            });
+
+            Some(serde_json::Value::Object(ret))
+        }
+        _ => Some(new.to_owned()),
+    }
+}
+
+// columns padded to their widest cell, under the uppercased headers even without rows
+pub fn format_table(headers: &[&str], rows: &[Vec<(&str, String)>]) -> String {
+    let headers = headers.iter().map(|e| e.to_uppercase()).collect::<Vec<_>>();
+    let cells = std::iter::once(headers)
+        .chain(
+            rows.iter()
+                .map(|e| e.iter().map(|(_, v)| v.to_owned()).collect()),
+        )
+        .collect::<Vec<_>>();
+
+    let widths = (0..cells[0].len())
+        .map(|i| cells.iter().map(|e| e[i].chars().count()).max().unwrap())
+        .collect::<Vec<_>>();
+
+    cells
+        .iter()
+        .map(|e| {
+            e.iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// replace `{column}` by a column of `list`, or `{metadata.layer}` by a field of the serialized widget,
+// `{{` and `}}` are literal braces
+pub fn format_template(
+    template: &str,
+    columns: &[(&str, String)],
+    value: &serde_json::Value,
+) -> Result<String, String> {
+    let mut ret = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find(|e: char| e == '{' || e == '}') {
+        ret.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            ret.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        if rest.starts_with('}') {
+            return Err(format!("unmatched }} in {}", template));
+        }
+
+        let end = rest
+            .find('}')
+            .ok_or(format!("unclosed {{ in {}", template))?;
+        let key = &rest[1..end];
+
+        let cell = match columns.iter().find(|(k, _)| *k == key) {
+            Some((_, v)) => v.to_owned(),
+            None => match key
+                .split('.')
+                .try_fold(value, |e, k| e.get(k))
+                .ok_or(format!("unknown field {}", key))?
+            {
+                serde_json::Value::String(e) => e.to_owned(),
+                e => e.to_string(),
+            },
+        };
+        ret.push_str(&cell);
+        rest = &rest[end + 1..];
+    }
+    ret.push_str(rest);
+
+    Ok(ret)
+}
+
+// a name, url, directory or monitor pattern of a query, compiled once for every widget
+enum Pattern<'a> {
+    Any,
+    Exact(&'a str),
+    Glob(&'a str),
+    // invalid patterns are rejected by `QueryArgs::resolve`
+    Regex(Option<Regex>),
+}
+
+impl<'a> Pattern<'a> {
+    fn new(mode: MatchMode, pattern: Option<&'a str>) -> Self {
+        let Some(pattern) = pattern else {
+            return Pattern::Any;
+        };
+
+        match mode {
+            MatchMode::Exact => Pattern::Exact(pattern),
+            MatchMode::Glob => Pattern::Glob(pattern),
+            MatchMode::Regex => Pattern::Regex(Regex::new(pattern).ok()),
+        }
+    }
+
+    fn matches(&self, text: Option<&str>) -> bool {
+        match (self, text) {
+            (Pattern::Any, _) => true,
+            (_, None) => false,
+            (Pattern::Exact(pattern), Some(text)) => *pattern == text,
+            (Pattern::Glob(pattern), Some(text)) => glob_match(pattern.as_bytes(), text.as_bytes()),
+            (Pattern::Regex(regex), Some(text)) => regex.as_ref().is_some_and(|e| e.is_match(text)),
+        }
+    }
+}
+
+// on a mismatch, only the last `*` takes one more character, so no input backtracks exponentially
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // the last `*` and the text position it matches up to
+    let mut star = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(b'?') => {
+                p += 1;
+                t += 1;
+            }
+            Some(e) if *e == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|e| *e == b'*')
+}
+
+// compiles the query's patterns, the returned filter is applied to every widget
+pub fn widget_filter(query: &QueryArgs) -> impl Fn(&Widget) -> bool + '_ {
+    let name = Pattern::new(query.mode, query.name.as_deref());
+    let url = Pattern::new(query.mode, query.url.as_deref());
+    let directory = Pattern::new(query.mode, query.directory.as_deref());
+    let monitor = Pattern::new(query.mode, query.monitor.as_deref());
+
+    move |widget| {
+        let ret = (query.id.is_none() || query.id.as_ref() == Some(&widget.id))
+            && name.matches(widget.name.as_deref())
+            && url.matches(Some(widget.url.as_str()))
+            && directory.matches(widget.directory())
+            && monitor.matches(widget.metadata.output.as_deref())
+            && query.visible.map_or(true, |e| e == widget.visible)
+            && (query.layer.is_none() || query.layer == widget.metadata.layer)
+            && query.tags.as_ref().map_or(true, |e| match query.all_tags {
+                true => e.iter().all(|t| widget.tags.contains(t)),
+                false => widget.tags.iter().any(|t| e.contains(t)),
+            });
+
+        ret != query.not
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(pattern: &str, text: &str) -> bool {
+        glob_match(pattern.as_bytes(), text.as_bytes())
+    }
+
+    #[test]
+    fn formats_templates() {
+        let columns = vec![("name", "bar".to_string())];
+        let value = serde_json::json!({ "metadata": { "layer": "top", "zoom": 1.5 } });
+
+        assert_eq!(
+            format_template("{name} {metadata.layer} {metadata.zoom}", &columns, &value),
+            Ok("bar top 1.5".to_string())
+        );
+        assert_eq!(
+            format_template("{{\"name\": \"{name}\"}}", &columns, &value),
+            Ok("{\"name\": \"bar\"}".to_string())
+        );
+        assert!(format_template("{missing}", &columns, &value).is_err());
+        assert!(format_template("{name", &columns, &value).is_err());
+        assert!(format_template("name}", &columns, &value).is_err());
+    }
+
+    #[test]
+    fn formats_tables() {
+        let rows = vec![
+            vec![("name", "bar".to_string()), ("id", "1".to_string())],
+            vec![("name", "clock".to_string()), ("id", "2".to_string())],
+        ];
+
+        assert_eq!(
+            format_table(&["name", "id"], &rows),
+            "NAME   ID\nbar    1\nclock  2"
+        );
+        // the headers are printed even when nothing matched
+        assert_eq!(format_table(&["name", "id"], &[]), "NAME  ID");
+    }
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob("", ""));
+        assert!(glob("*", ""));
+        assert!(glob("bar-*", "bar-top"));
+        assert!(glob("*-top", "bar-top"));
+        assert!(glob("b?r*p", "bar-top"));
+        assert!(glob("*a*b*", "xaxxbx"));
+        assert!(!glob("bar-?", "bar-"));
+        assert!(!glob("*a*b", "xaxxbx"));
+        assert!(!glob("bar", "bar-top"));
+    }
+
+    #[test]
+    fn matches_globs_without_backtracking_every_star() {
+        let text = "a".repeat(10_000);
+        let pattern = format!("{}b", "*a".repeat(50));
+
+        assert!(!glob(&pattern, &text));
+    }
 }
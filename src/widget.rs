@@ -1,26 +1,53 @@
 use crate::app_state::AppState;
-use crate::cli::{CliCommand, WidgetDefaultSize, WidgetMargins, WidgetMetadataArgs};
-use crate::constants::SOCKET_PATH;
-use crate::utils::read_socket_response;
+use crate::bridge::{
+    BridgeCommand, BridgeCommands, InputRect, InternalCommands, ScriptCommands, WidgetMessage,
+};
+use crate::cli::{
+    CliCommand, DaemonOptions, WidgetAnchor, WidgetAutoSize, WidgetAutohide, WidgetAutohideMode,
+    WidgetDefaultSize, WidgetKeyboardMode, WidgetLayer, WidgetMargins, WidgetMetadataArgs,
+    WidgetSizeLimits, WidgetTransition, WidgetTransitionKind,
+};
+use crate::constants::{SOCKET_PATH, WIDGET_SERVER_URL};
+use crate::length::Length;
+use crate::monitors::{monitor_connector, resolve_monitor};
+use crate::permissions::Capability;
+use crate::services::compositor::start_compositor_service;
+use crate::services::monitors::start_monitor_service;
+use crate::services::network::start_network_service;
+use crate::services::power::start_power_service;
+use crate::services::system::start_system_service;
+use crate::utils::{json_diff, read_socket_response};
 use crate::{cli::CliCommands, utils::write_socket_message};
 use async_std::channel;
 use async_std::os::unix::net::UnixListener;
 use gdk::cairo::{RectangleInt, Region};
 use gdk::gio::{prelude::*, ApplicationFlags};
-use gdk::Display;
-use glib::{clone, SignalHandlerId};
+use gdk::{Display, Monitor};
+use glib::clone;
 use gtk::glib;
 use gtk::prelude::*;
 use gtk::Application;
 use gtk::ApplicationWindow;
 use gtk_layer_shell::{Edge, KeyboardMode, Layer, LayerShell};
-use serde::Serialize;
-use std::cell::RefCell;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
 use std::rc::Rc;
+use std::time::Duration;
 use uuid::Uuid;
-use webkit2gtk::{SettingsExt, UserContentManagerExt, WebInspectorExt, WebView, WebViewExt};
-
-#[derive(Debug, Serialize, PartialEq, Default)]
+use webkit2gtk::{
+    LoadEvent, SettingsExt, SnapshotOptions, SnapshotRegion, UserContentInjectedFrames,
+    UserContentManagerExt, UserScript, UserScriptInjectionTime, WebInspectorExt, WebView,
+    WebViewExt,
+};
+
+// side of the square cells the alpha channel is sampled in
+const ALPHA_CELL: i32 = 4;
+// minimum time between two snapshots while the page keeps repainting
+const ALPHA_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq, Default)]
 pub struct WidgetMetadataAnchors {
     top: bool,
     right: bool,
@@ -28,48 +55,160 @@ pub struct WidgetMetadataAnchors {
     left: bool,
 }
 
-#[derive(Debug, Serialize, PartialEq, Default)]
+// resolved pixels next to the lengths they were requested as
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq, Default)]
 pub struct WidgetMetadataMargins {
     pub top: i32,
     pub right: i32,
     pub bottom: i32,
     pub left: i32,
+    pub requested: WidgetMargins,
 }
 
-#[derive(Debug, Serialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub struct WidgetMetadataSize {
     pub width: i32,
     pub height: i32,
+    pub requested: WidgetDefaultSize,
+}
+
+// the window size an auto sized widget was fitted to, -1 on an axis left to the configured size
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct WidgetMetadataFittedSize {
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq, Default)]
+pub struct WidgetMetadataSizeLimits {
+    pub min_width: Option<i32>,
+    pub max_width: Option<i32>,
+    pub min_height: Option<i32>,
+    pub max_height: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct WidgetMetadataAutohide {
+    pub mode: WidgetAutohideMode,
+    pub size: i32,
+    pub delay: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct WidgetMetadataTransition {
+    pub kind: WidgetTransitionKind,
+    pub duration: u64,
+    pub easing: String,
 }
 
-#[derive(Debug, Serialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub struct WidgetMetadata {
-    pub monitor: Option<i32>,
-    #[serde(skip_serializing, skip_deserializing)]
-    pub layer: Option<Layer>,
+    // the requested selector, see `resolve_monitor`
+    pub monitor: Option<String>,
+    // connector of the monitor the selector resolved to
+    pub output: Option<String>,
+    pub layer: Option<WidgetLayer>,
     pub anchors: Option<WidgetMetadataAnchors>,
     pub margins: Option<WidgetMetadataMargins>,
     pub size: Option<WidgetMetadataSize>,
+    // the page reports its content size on these axes, never `none`
+    pub auto_size: Option<WidgetAutoSize>,
+    pub size_limits: Option<WidgetMetadataSizeLimits>,
+    // kept apart from `size`, which stays what was configured
+    pub fitted_size: Option<WidgetMetadataFittedSize>,
     pub click_through: bool,
+    pub alpha_passthrough: bool,
     pub exclusive: bool,
-    pub keyboard_mode: Option<String>,
+    pub autohide: Option<WidgetMetadataAutohide>,
+    pub transition: Option<WidgetMetadataTransition>,
+    pub keyboard_mode: Option<WidgetKeyboardMode>,
+    pub zoom: f64,
+    pub zoom_dpi: bool,
+    pub follow_ui_scale: bool,
+    // the zoom level applied to the webview, see `sync_zoom`
+    pub zoom_level: f64,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct Widget {
     pub id: String,
+    // unique among widgets, shared by the clones of `--monitor all`
+    pub name: Option<String>,
     pub tags: Vec<String>,
     pub url: String,
+    pub permissions: Vec<Capability>,
 
     // gtk info
-    #[serde(skip_serializing)]
+    #[serde(skip)]
     pub window: ApplicationWindow,
-    #[serde(skip_serializing)]
+    #[serde(skip)]
     webview: WebView,
     pub metadata: WidgetMetadata,
 
-    #[serde(skip_serializing)]
-    signal_handler: Option<SignalHandlerId>,
+    #[serde(skip)]
+    user_script: Option<UserScript>,
+    // window.www as last sent to the page
+    #[serde(skip)]
+    snapshot: serde_json::Value,
+
+    // input region marked by the page, the whole window accepts input when none
+    #[serde(skip)]
+    interactive: Option<Vec<RectangleInt>>,
+    // opaque parts of the last snapshot, shared with the draw handler which takes them
+    #[serde(skip)]
+    opaque: Option<Vec<RectangleInt>>,
+    #[serde(skip)]
+    alpha_passthrough: Rc<Cell<bool>>,
+    #[serde(skip)]
+    autohide: Rc<RefCell<Option<Autohide>>>,
+    // bumped by show, so a pending unmap from an earlier hide is dropped
+    #[serde(skip)]
+    transitions: Rc<Cell<u64>>,
+    // whether the last load failed, reset when the next one starts
+    #[serde(skip)]
+    load_failed: Rc<Cell<bool>>,
+    // content size last reported by the page, in css pixels
+    #[serde(skip)]
+    content_size: Option<(i32, i32)>,
+
+    // topics pushed to this widget by the daemon
+    #[serde(skip)]
+    pub subscriptions: HashSet<String>,
+
+    // shown or hidden by the user, the window stays unmapped while its monitor is missing
+    pub visible: bool,
+    #[serde(skip)]
+    placed: bool,
+    // clones made by `--monitor all` share the id of the first one, each pinned to a monitor
+    #[serde(skip)]
+    pub group: Option<String>,
+    #[serde(skip)]
+    pub pinned: Option<Monitor>,
+    // what the monitor selector resolved to
+    #[serde(skip)]
+    monitor: Option<Monitor>,
+    // the global ui scale of the daemon
+    #[serde(skip)]
+    ui_scale: f64,
+}
+
+// state shared with the pointer handlers of an autohiding window
+#[derive(Debug)]
+struct Autohide {
+    mode: WidgetAutohideMode,
+    edge: Edge,
+    size: i32,
+    delay: Duration,
+    // margin and size the widget was configured with, restored when revealed
+    margin: i32,
+    width: i32,
+    height: i32,
+    revealed: bool,
+    // bumped on every pointer crossing, so a pending hide knows it is stale
+    generation: u64,
+    // the input region of the widget, see `update_autohide_input_region`
+    click_through: bool,
+    interactive: Option<Vec<RectangleInt>>,
 }
 
 fn create_window(app: &Application) -> ApplicationWindow {
@@ -84,28 +223,27 @@ fn create_window(app: &Application) -> ApplicationWindow {
     window
 }
 
-fn inject_javascript_to_webview(widget: &Widget) -> SignalHandlerId {
-    let template = r#"window.www = {{www}};"#
-        .replace("{{www}}", serde_json::to_string(widget).unwrap().as_str());
+fn inject_javascript_to_webview(webview: &WebView, www: &serde_json::Value) -> UserScript {
+    let template = r#"window.www = Object.assign(window.www || {}, {{www}});"#
+        .replace("{{www}}", serde_json::to_string(www).unwrap().as_str());
+
+    // run at document start, so the page never sees a missing or stale window.www
+    let script = UserScript::new(
+        template.as_str(),
+        UserContentInjectedFrames::TopFrame,
+        UserScriptInjectionTime::Start,
+        &[],
+        &[],
+    );
+    webview.user_content_manager().unwrap().add_script(&script);
 
-    widget
-        .webview
-        .connect_load_changed(move |webview, load_event| {
-            if load_event == webkit2gtk::LoadEvent::Finished {
-                webview.run_javascript(
-                    template.as_str(),
-                    gdk::gio::Cancellable::NONE,
-                    |_| {},
-                );
-            }
-        })
+    script
 }
 
-fn create_webview(url: String) -> WebView {
+fn create_webview() -> WebView {
     let webview = WebView::new();
 
     webview.set_background_color(&gdk::RGBA::new(0.0, 0.0, 0.0, 0.0));
-    webview.load_uri(url.as_str());
 
     webview
 }
@@ -114,9 +252,57 @@ fn apply_layer_shell(window: &ApplicationWindow) {
     window.init_layer_shell();
 }
 
-fn apply_javascript_api(webview: &WebView, api: async_std::channel::Sender<String>) {
+fn apply_load_handlers(
+    webview: &WebView,
+    id: String,
+    load_failed: Rc<Cell<bool>>,
+    api: async_std::channel::Sender<WidgetMessage>,
+) {
+    webview.connect_load_changed(clone!(@strong load_failed => move |_, event| {
+        if event == LoadEvent::Started {
+            load_failed.set(false);
+            // the new page subscribes again once its scripts run
+            let _ = api.send_blocking(WidgetMessage {
+                sender: id.to_owned(),
+                command: ScriptCommands::Internal(InternalCommands::LoadStarted),
+            });
+        }
+    }));
+    // keep the default error page
+    webview.connect_load_failed(move |_, _, uri, e| {
+        eprintln!("failed to load {}: {}", uri, e);
+        load_failed.set(true);
+        false
+    });
+}
+
+fn dispatch_event<T: Serialize>(webview: &WebView, topic: &str, detail: &T) {
+    let event = serde_json::to_string(&format!("www:{}", topic)).unwrap();
+    let detail = serde_json::to_string(detail).unwrap();
+    let script = r#"window.dispatchEvent(new CustomEvent({{event}}, { detail: {{detail}} }));"#
+        .replace("{{event}}", event.as_str())
+        .replace("{{detail}}", detail.as_str());
+
+    webview.run_javascript(script.as_str(), gdk::gio::Cancellable::NONE, |_| {});
+}
+
+fn apply_javascript_api(
+    webview: &WebView,
+    id: String,
+    permissions: Vec<Capability>,
+    api: async_std::channel::Sender<WidgetMessage>,
+) {
     let ucm = webview.user_content_manager().unwrap();
 
+    // expose the page side of the bridge before any page script runs
+    ucm.add_script(&UserScript::new(
+        include_str!("www.js"),
+        UserContentInjectedFrames::TopFrame,
+        UserScriptInjectionTime::Start,
+        &[],
+        &[],
+    ));
+
     let (tx, rx) = channel::unbounded();
 
     ucm.connect_script_message_received(Some("widget"), move |_, jsr| {
@@ -128,127 +314,278 @@ fn apply_javascript_api(webview: &WebView, api: async_std::channel::Sender<Strin
 
     glib::spawn_future_local(clone!(@strong webview => async move {
         while let Ok(ret) = rx.recv().await {
-            // parse it to CliCommands
-            let _ = api.send_blocking(ret);
+            let command = match serde_json::from_str::<ScriptCommands>(ret.as_str()) {
+                Ok(command) => command,
+                Err(e) => {
+                    eprintln!("invalid message from {}: {}", id, e);
+                    continue;
+                }
+            };
+
+            // enforce the widget's grants before the command reaches the daemon
+            if let Some(capability) = command
+                .required_capability()
+                .filter(|e| !permissions.contains(e))
+            {
+                let call = match &command {
+                    ScriptCommands::Bridge(BridgeCommands::Call { id, .. }) => Some(*id),
+                    _ => None,
+                };
+                dispatch_event(
+                    &webview,
+                    "denied",
+                    &serde_json::json!({ "capability": capability, "call": call }),
+                );
+                continue;
+            }
+
+            // tag the message with the widget which sent it
+            let _ = api.send_blocking(WidgetMessage {
+                sender: id.to_owned(),
+                command,
+            });
         }
     }));
 
     ucm.register_script_message_handler("widget");
 }
 
-fn update_monitor(window: &ApplicationWindow, monitor: i32) -> i32 {
-    let display = &Display::default().expect("failed to get display");
-    let target_monitor = std::cmp::min(std::cmp::max(monitor, 0), display.n_monitors() - 1);
-    window.set_monitor(display.monitor(target_monitor).unwrap().as_ref());
-    target_monitor
+// row runs of cells holding any non transparent pixel, in page coordinates
+fn opaque_rectangles(surface: gdk::cairo::Surface) -> Option<Vec<InputRect>> {
+    let source = gdk::cairo::ImageSurface::try_from(surface).ok()?;
+    let (scale, _) = source.device_scale();
+    let width = ((source.width() as f64 / scale) as i32 + ALPHA_CELL - 1) / ALPHA_CELL;
+    let height = ((source.height() as f64 / scale) as i32 + ALPHA_CELL - 1) / ALPHA_CELL;
+
+    let mut cells =
+        gdk::cairo::ImageSurface::create(gdk::cairo::Format::ARgb32, width, height).ok()?;
+    {
+        let context = gdk::cairo::Context::new(&cells).ok()?;
+        context.scale(1.0 / ALPHA_CELL as f64, 1.0 / ALPHA_CELL as f64);
+        context.set_source_surface(&source, 0.0, 0.0).ok()?;
+        context.paint().ok()?;
+    }
+    cells.flush();
+
+    let stride = cells.stride() as usize;
+    let data = cells.data().ok()?;
+    let mut ret = vec![];
+
+    for y in 0..height {
+        let mut start = None;
+
+        for x in 0..=width {
+            let opaque = x < width && {
+                let i = y as usize * stride + x as usize * 4;
+                u32::from_ne_bytes(data[i..i + 4].try_into().unwrap()) >> 24 > 0
+            };
+
+            match (opaque, start) {
+                (true, None) => start = Some(x),
+                (false, Some(begin)) => {
+                    ret.push(InputRect {
+                        x: begin * ALPHA_CELL,
+                        y: y * ALPHA_CELL,
+                        width: (x - begin) * ALPHA_CELL,
+                        height: ALPHA_CELL,
+                    });
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Some(ret)
 }
 
-fn update_layer(window: &ApplicationWindow, layer: String) -> Layer {
-    let layer = match layer.as_str() {
-        "background" => Layer::Background,
-        "bottom" => Layer::Bottom,
-        "top" => Layer::Top,
-        "overlay" => Layer::Overlay,
-        _ => Layer::Background,
-    };
+// snapshot the page after it repaints, at most once per interval, and report its opaque parts
+fn apply_alpha_passthrough(
+    webview: &WebView,
+    id: String,
+    enabled: Rc<Cell<bool>>,
+    api: async_std::channel::Sender<WidgetMessage>,
+) {
+    let pending = Rc::new(Cell::new(false));
+
+    webview.connect_draw(move |webview, _| {
+        if enabled.get() && !pending.replace(true) {
+            glib::timeout_add_local_once(
+                ALPHA_INTERVAL,
+                clone!(@strong webview, @strong id, @strong pending, @strong api => move || {
+                    pending.set(false);
+                    webview.snapshot(
+                        SnapshotRegion::Visible,
+                        SnapshotOptions::TRANSPARENT_BACKGROUND,
+                        gdk::gio::Cancellable::NONE,
+                        move |surface| {
+                            let Some(rects) = surface.ok().and_then(opaque_rectangles) else {
+                                return;
+                            };
+                            let _ = api.send_blocking(WidgetMessage {
+                                sender: id,
+                                command: ScriptCommands::Internal(
+                                    InternalCommands::OpaqueRegion { rects },
+                                ),
+                            });
+                        },
+                    );
+                }),
+            );
+        }
 
-    window.set_layer(layer);
+        glib::Propagation::Proceed
+    });
+}
 
-    layer
+fn update_monitor(window: &ApplicationWindow, monitor: &Monitor) -> Option<String> {
+    window.set_monitor(monitor);
+    monitor_connector(monitor)
 }
 
-fn update_margins(window: &ApplicationWindow, margins: &WidgetMargins) -> WidgetMetadataMargins {
-    let mut ret = WidgetMetadataMargins::default();
+fn update_layer(window: &ApplicationWindow, layer: WidgetLayer) -> WidgetLayer {
+    window.set_layer(layer.into());
 
-    if let Some(top) = margins.top {
-        window.set_layer_shell_margin(Edge::Top, top);
-        ret.top = top;
-    }
+    layer
+}
 
-    if let Some(right) = margins.right {
-        window.set_layer_shell_margin(Edge::Right, right);
-        ret.right = right;
+fn resolve_length(length: &Option<Length>, extent: i32) -> Option<i32> {
+    match length.as_ref()?.resolve(extent) {
+        Ok(pixels) => Some(pixels),
+        Err(e) => {
+            eprintln!("failed to resolve {}: {}", length.as_ref().unwrap(), e);
+            None
+        }
     }
+}
 
-    if let Some(bottom) = margins.bottom {
-        window.set_layer_shell_margin(Edge::Bottom, bottom);
-        ret.bottom = bottom;
+fn merge_margins(
+    current: Option<&WidgetMetadataMargins>,
+    margins: &WidgetMargins,
+) -> WidgetMargins {
+    let current = current.map(|e| e.requested.to_owned()).unwrap_or_default();
+
+    WidgetMargins {
+        top: margins.top.to_owned().or(current.top),
+        right: margins.right.to_owned().or(current.right),
+        bottom: margins.bottom.to_owned().or(current.bottom),
+        left: margins.left.to_owned().or(current.left),
     }
+}
 
-    if let Some(left) = margins.left {
-        window.set_layer_shell_margin(Edge::Left, left);
-        ret.left = left;
+// percentages of the top and bottom margins follow the monitor height, the others its width
+fn resolve_margins(margins: &WidgetMargins, (width, height): (i32, i32)) -> WidgetMetadataMargins {
+    WidgetMetadataMargins {
+        top: resolve_length(&margins.top, height).unwrap_or_default(),
+        right: resolve_length(&margins.right, width).unwrap_or_default(),
+        bottom: resolve_length(&margins.bottom, height).unwrap_or_default(),
+        left: resolve_length(&margins.left, width).unwrap_or_default(),
+        requested: margins.to_owned(),
     }
+}
+
+fn update_margins(
+    window: &ApplicationWindow,
+    margins: &WidgetMargins,
+    extent: (i32, i32),
+) -> WidgetMetadataMargins {
+    let ret = resolve_margins(margins, extent);
+
+    window.set_layer_shell_margin(Edge::Top, ret.top);
+    window.set_layer_shell_margin(Edge::Right, ret.right);
+    window.set_layer_shell_margin(Edge::Bottom, ret.bottom);
+    window.set_layer_shell_margin(Edge::Left, ret.left);
 
     ret
 }
 
-fn update_anchors(window: &ApplicationWindow, anchors: &Vec<String>) -> WidgetMetadataAnchors {
-    let mut ret = WidgetMetadataAnchors::default();
-
-    [Edge::Top, Edge::Right, Edge::Bottom, Edge::Left]
-        .iter()
-        .for_each(|e| window.set_anchor(*e, false));
+fn update_anchors(window: &ApplicationWindow, anchors: &[WidgetAnchor]) -> WidgetMetadataAnchors {
+    let ret = WidgetMetadataAnchors {
+        top: anchors.contains(&WidgetAnchor::Top),
+        right: anchors.contains(&WidgetAnchor::Right),
+        bottom: anchors.contains(&WidgetAnchor::Bottom),
+        left: anchors.contains(&WidgetAnchor::Left),
+    };
 
-    anchors.iter().for_each(|a| {
-        window.set_anchor(
-            match a.as_str() {
-                "top" => {
-                    ret.top = true;
-                    Edge::Top
-                }
-                "right" => {
-                    ret.right = true;
-                    Edge::Right
-                }
-                "bottom" => {
-                    ret.bottom = true;
-                    Edge::Bottom
-                }
-                "left" => {
-                    ret.left = true;
-                    Edge::Left
-                }
-                _ => {
-                    ret.top = true;
-                    Edge::Top
-                }
-            },
-            true,
-        );
-    });
+    window.set_anchor(Edge::Top, ret.top);
+    window.set_anchor(Edge::Right, ret.right);
+    window.set_anchor(Edge::Bottom, ret.bottom);
+    window.set_anchor(Edge::Left, ret.left);
 
     ret
 }
 
-fn update_size(window: &ApplicationWindow, size: &WidgetDefaultSize) -> WidgetMetadataSize {
-    let mut ret = WidgetMetadataSize {
-        width: -1,
-        height: -1,
-    };
+fn merge_size(current: Option<&WidgetMetadataSize>, size: &WidgetDefaultSize) -> WidgetDefaultSize {
+    let current = current.map(|e| e.requested.to_owned()).unwrap_or_default();
 
-    if let Some(width) = size.width {
-        window.set_width_request(width);
-        ret.width = width;
+    WidgetDefaultSize {
+        width: size.width.to_owned().or(current.width),
+        height: size.height.to_owned().or(current.height),
     }
+}
 
-    if let Some(height) = size.height {
-        window.set_height_request(height);
-        ret.height = height;
+// -1 leaves an axis to the content
+fn resolve_size(size: &WidgetDefaultSize, (width, height): (i32, i32)) -> WidgetMetadataSize {
+    WidgetMetadataSize {
+        width: resolve_length(&size.width, width).unwrap_or(-1),
+        height: resolve_length(&size.height, height).unwrap_or(-1),
+        requested: size.to_owned(),
     }
+}
+
+fn update_size(
+    window: &ApplicationWindow,
+    size: &WidgetDefaultSize,
+    extent: (i32, i32),
+) -> WidgetMetadataSize {
+    let ret = resolve_size(size, extent);
+
+    window.set_width_request(ret.width);
+    window.set_height_request(ret.height);
 
     ret
 }
 
-fn update_click_through(window: &ApplicationWindow, click_through: bool) -> bool {
-    if click_through {
-        let rectangle_int = RectangleInt::new(0, 0, 0, 0);
-        let rectangle = Region::create_rectangle(&rectangle_int);
-        window.input_shape_combine_region(Some(&rectangle));
-        true
-    } else {
-        window.input_shape_combine_region(None);
-        false
+fn update_size_limits(limits: &WidgetSizeLimits) -> WidgetMetadataSizeLimits {
+    WidgetMetadataSizeLimits {
+        min_width: limits.min_width,
+        max_width: limits.max_width,
+        min_height: limits.min_height,
+        max_height: limits.max_height,
+    }
+}
+
+// css pixels of the page to pixels of the window
+fn zoom_rectangle(rect: &InputRect, zoom: f64) -> RectangleInt {
+    let x = (rect.x as f64 * zoom).floor() as i32;
+    let y = (rect.y as f64 * zoom).floor() as i32;
+    let right = ((rect.x + rect.width) as f64 * zoom).ceil() as i32;
+    let bottom = ((rect.y + rect.height) as f64 * zoom).ceil() as i32;
+
+    RectangleInt::new(x, y, right - x, bottom - y)
+}
+
+fn clamp_size(value: i32, min: Option<i32>, max: Option<i32>) -> i32 {
+    let value = min.map_or(value, |e| value.max(e));
+    max.map_or(value, |e| value.min(e))
+}
+
+fn update_input_region(
+    window: &ApplicationWindow,
+    click_through: bool,
+    interactive: Option<&Vec<RectangleInt>>,
+) {
+    match (click_through, interactive) {
+        (true, _) => {
+            let rectangle_int = RectangleInt::new(0, 0, 0, 0);
+            let rectangle = Region::create_rectangle(&rectangle_int);
+            window.input_shape_combine_region(Some(&rectangle));
+        }
+        (false, Some(rectangles)) => {
+            let region = Region::create_rectangles(rectangles);
+            window.input_shape_combine_region(Some(&region));
+        }
+        (false, None) => window.input_shape_combine_region(None),
     }
 }
 
@@ -262,24 +599,539 @@ fn update_exclusive(window: &ApplicationWindow, exclusive: bool) -> bool {
     }
 }
 
-fn update_keyboard_mode(window: &ApplicationWindow, keyboard_mode: String) -> String {
-    // window.set_keyboard_interactivity(keyboard_interactivity);
-    match keyboard_mode.as_str() {
-        "none" => window.set_keyboard_mode(KeyboardMode::None),
-        "on-demand" => window.set_keyboard_mode(KeyboardMode::OnDemand),
-        "exclusive" => window.set_keyboard_mode(KeyboardMode::Exclusive),
-        _ => window.set_keyboard_mode(KeyboardMode::None),
+fn update_autohide(
+    current: Option<&WidgetMetadataAutohide>,
+    autohide: &WidgetAutohide,
+) -> Option<WidgetMetadataAutohide> {
+    let mode = autohide.mode.or(current.map(|e| e.mode))?;
+
+    match mode {
+        WidgetAutohideMode::None => None,
+        _ => Some(WidgetMetadataAutohide {
+            mode,
+            size: autohide.size.or(current.map(|e| e.size)).unwrap_or(4),
+            delay: autohide.delay.or(current.map(|e| e.delay)).unwrap_or(500),
+        }),
+    }
+}
+
+// the edge a widget hides behind, an anchored edge whose opposite is free
+fn autohide_edge(anchors: &WidgetMetadataAnchors) -> Option<Edge> {
+    [
+        (anchors.top, anchors.bottom, Edge::Top),
+        (anchors.bottom, anchors.top, Edge::Bottom),
+        (anchors.left, anchors.right, Edge::Left),
+        (anchors.right, anchors.left, Edge::Right),
+    ]
+    .into_iter()
+    .find(|(anchored, opposite, _)| *anchored && !*opposite)
+    .map(|e| e.2)
+}
+
+fn apply_autohide(window: &ApplicationWindow, state: &Autohide) {
+    let vertical = matches!(state.edge, Edge::Top | Edge::Bottom);
+
+    match (state.mode, state.revealed) {
+        (WidgetAutohideMode::Collapse, false) => {
+            let (width, height) = match vertical {
+                true => (state.width, state.size),
+                false => (state.size, state.height),
+            };
+            window.set_size_request(width, height);
+            window.resize(width.max(1), height.max(1));
+        }
+        (WidgetAutohideMode::Slide, false) => {
+            // negative margins move the surface past the edge of the output
+            let extent = match vertical {
+                true => window.allocated_height().max(state.height),
+                false => window.allocated_width().max(state.width),
+            };
+            window.set_layer_shell_margin(state.edge, state.margin - (extent - state.size).max(0));
+        }
+        _ => {
+            window.set_size_request(state.width, state.height);
+            window.set_layer_shell_margin(state.edge, state.margin);
+        }
     }
+
+    update_autohide_input_region(window, state);
+}
+
+// the strip takes input whatever the widget's input region, so the pointer can reveal the widget
+// and resting on the strip doesn't hide it again
+fn update_autohide_input_region(window: &ApplicationWindow, state: &Autohide) {
+    if !state.revealed || (!state.click_through && state.interactive.is_none()) {
+        window.input_shape_combine_region(None);
+        return;
+    }
+
+    let (width, height) = (window.allocated_width(), window.allocated_height());
+    let mut rectangles = vec![match state.edge {
+        Edge::Top => RectangleInt::new(0, 0, width, state.size),
+        Edge::Bottom => RectangleInt::new(0, height - state.size, width, state.size),
+        Edge::Left => RectangleInt::new(0, 0, state.size, height),
+        _ => RectangleInt::new(width - state.size, 0, state.size, height),
+    }];
+    if !state.click_through {
+        rectangles.extend(state.interactive.iter().flatten().copied());
+    }
+
+    let region = Region::create_rectangles(&rectangles);
+    window.input_shape_combine_region(Some(&region));
+}
+
+// the hidden surface only gets pointer events on its strip, so reveal and hide from here
+fn apply_autohide_handlers(
+    window: &ApplicationWindow,
+    webview: &WebView,
+    autohide: Rc<RefCell<Option<Autohide>>>,
+) {
+    window.add_events(gdk::EventMask::ENTER_NOTIFY_MASK | gdk::EventMask::LEAVE_NOTIFY_MASK);
+
+    // the strip follows the size of the window
+    window.connect_size_allocate(clone!(@strong autohide => move |window, _| {
+        // the pointer handlers resize the window while holding the state
+        if let Ok(state) = autohide.try_borrow() {
+            if let Some(state) = state.as_ref() {
+                update_autohide_input_region(window, state);
+            }
+        }
+    }));
+
+    window.connect_enter_notify_event(
+        clone!(@strong webview, @strong autohide => move |window, _| {
+            if let Some(state) = autohide.borrow_mut().as_mut() {
+                state.generation += 1;
+
+                if !state.revealed {
+                    state.revealed = true;
+                    apply_autohide(window, state);
+                    dispatch_event(&webview, "autohide", &serde_json::json!({ "revealed": true }));
+                }
+            }
+
+            glib::Propagation::Proceed
+        }),
+    );
+
+    window.connect_leave_notify_event(clone!(@strong webview => move |window, event| {
+        // moving onto the webview is not leaving the window
+        if event.detail() == gdk::NotifyType::Inferior {
+            return glib::Propagation::Proceed;
+        }
+
+        let pending = autohide.borrow_mut().as_mut().map(|e| {
+            e.generation += 1;
+            (e.generation, e.delay)
+        });
+
+        if let Some((generation, delay)) = pending {
+            glib::timeout_add_local_once(
+                delay,
+                clone!(@strong window, @strong webview, @strong autohide => move || {
+                    if let Some(state) = autohide
+                        .borrow_mut()
+                        .as_mut()
+                        .filter(|e| e.generation == generation && e.revealed)
+                    {
+                        state.revealed = false;
+                        apply_autohide(&window, state);
+                        dispatch_event(&webview, "autohide", &serde_json::json!({ "revealed": false }));
+                    }
+                }),
+            );
+        }
+
+        glib::Propagation::Proceed
+    }));
+}
+
+fn update_transition(
+    current: Option<&WidgetMetadataTransition>,
+    transition: &WidgetTransition,
+) -> Option<WidgetMetadataTransition> {
+    let kind = transition.kind.or(current.map(|e| e.kind))?;
+    match kind {
+        WidgetTransitionKind::None => None,
+        _ => Some(WidgetMetadataTransition {
+            kind,
+            duration: transition
+                .duration
+                .or(current.map(|e| e.duration))
+                .unwrap_or(200),
+            easing: transition
+                .easing
+                .to_owned()
+                .or(current.map(|e| e.easing.to_owned()))
+                .unwrap_or("ease-out".to_string()),
+        }),
+    }
+}
+
+fn update_keyboard_mode(
+    window: &ApplicationWindow,
+    keyboard_mode: WidgetKeyboardMode,
+) -> WidgetKeyboardMode {
+    window.set_keyboard_mode(keyboard_mode.into());
+
     keyboard_mode
 }
 
+impl From<WidgetLayer> for Layer {
+    fn from(layer: WidgetLayer) -> Self {
+        match layer {
+            WidgetLayer::Background => Layer::Background,
+            WidgetLayer::Bottom => Layer::Bottom,
+            WidgetLayer::Top => Layer::Top,
+            WidgetLayer::Overlay => Layer::Overlay,
+        }
+    }
+}
+
+impl From<WidgetKeyboardMode> for KeyboardMode {
+    fn from(keyboard_mode: WidgetKeyboardMode) -> Self {
+        match keyboard_mode {
+            WidgetKeyboardMode::None => KeyboardMode::None,
+            WidgetKeyboardMode::Exclusive => KeyboardMode::Exclusive,
+            WidgetKeyboardMode::OnDemand => KeyboardMode::OnDemand,
+        }
+    }
+}
+
+impl From<&WidgetMetadataAnchors> for Vec<WidgetAnchor> {
+    fn from(anchors: &WidgetMetadataAnchors) -> Self {
+        [
+            (anchors.top, WidgetAnchor::Top),
+            (anchors.right, WidgetAnchor::Right),
+            (anchors.bottom, WidgetAnchor::Bottom),
+            (anchors.left, WidgetAnchor::Left),
+        ]
+        .into_iter()
+        .filter(|(anchored, _)| *anchored)
+        .map(|(_, e)| e)
+        .collect()
+    }
+}
+
+// the arguments reproducing the metadata on a new widget, lengths as they were requested
+impl From<&WidgetMetadata> for WidgetMetadataArgs {
+    fn from(metadata: &WidgetMetadata) -> Self {
+        Self {
+            monitor: metadata.monitor.to_owned(),
+            layer: metadata.layer,
+            anchors: metadata.anchors.as_ref().map(|e| e.into()),
+            margins: metadata.margins.as_ref().map(|e| e.requested.to_owned()),
+            size: metadata.size.as_ref().map(|e| e.requested.to_owned()),
+            auto_size: metadata.auto_size,
+            size_limits: metadata.size_limits.as_ref().map(|e| WidgetSizeLimits {
+                min_width: e.min_width,
+                max_width: e.max_width,
+                min_height: e.min_height,
+                max_height: e.max_height,
+            }),
+            click_through: Some(metadata.click_through),
+            alpha_passthrough: Some(metadata.alpha_passthrough),
+            exclusive: Some(metadata.exclusive),
+            autohide: metadata.autohide.as_ref().map(|e| WidgetAutohide {
+                mode: Some(e.mode),
+                size: Some(e.size),
+                delay: Some(e.delay),
+            }),
+            transition: metadata.transition.as_ref().map(|e| WidgetTransition {
+                kind: Some(e.kind),
+                duration: Some(e.duration),
+                easing: Some(e.easing.to_owned()),
+            }),
+            keyboard_mode: metadata.keyboard_mode,
+            zoom: Some(metadata.zoom),
+            zoom_dpi: Some(metadata.zoom_dpi),
+            follow_ui_scale: Some(metadata.follow_ui_scale),
+        }
+    }
+}
+
+// the headers of `list`, in the order of `Widget::columns`
+pub const COLUMNS: [&str; 9] = [
+    "name",
+    "id",
+    "source",
+    "tags",
+    "layer",
+    "monitor",
+    "visible",
+    "state",
+    "permissions",
+];
+
 impl Widget {
-    pub fn show(&self) {
-        self.window.show_all();
+    // `self:visibility` is the widget's own, apart from VISIBILITY_TOPIC covering every widget
+    pub fn show(&mut self) {
+        if !self.visible {
+            self.emit("self:visibility", &serde_json::json!({ "visible": true }));
+        }
+        self.visible = true;
+        self.transitions.set(self.transitions.get() + 1);
+        // mapped by `place` once its monitor is connected
+        if self.placed {
+            self.window.show_all();
+        }
+        self.play_transition("show");
+        self.sync_javascript();
+    }
+
+    // with a transition, the window is unmapped once the page played it
+    pub fn hide(&mut self) {
+        if self.visible {
+            self.emit("self:visibility", &serde_json::json!({ "visible": false }));
+        }
+        self.visible = false;
+        let duration = self.transition_duration();
+
+        if duration.is_zero() {
+            self.window.hide();
+        } else {
+            self.play_transition("hide");
+
+            let generation = self.transitions.get();
+            let transitions = self.transitions.clone();
+            glib::timeout_add_local_once(
+                duration,
+                clone!(@strong self.window as window => move || {
+                    if transitions.get() == generation {
+                        window.hide();
+                    }
+                }),
+            );
+        }
+
+        self.sync_javascript();
+    }
+
+    pub fn transition_duration(&self) -> Duration {
+        self.metadata
+            .transition
+            .as_ref()
+            .map_or(Duration::ZERO, |e| Duration::from_millis(e.duration))
+    }
+
+    // the page animates its root element, see `www:transition` in www.js
+    fn play_transition(&self, phase: &str) {
+        let Some(transition) = &self.metadata.transition else {
+            return;
+        };
+        let edge = match self.metadata.anchors.as_ref().and_then(autohide_edge) {
+            Some(Edge::Right) => "right",
+            Some(Edge::Bottom) => "bottom",
+            Some(Edge::Left) => "left",
+            _ => "top",
+        };
+
+        dispatch_event(
+            &self.webview,
+            "transition",
+            &serde_json::json!({
+                "phase": phase,
+                "kind": transition.kind,
+                "duration": transition.duration,
+                "easing": transition.easing,
+                "edge": edge,
+            }),
+        );
+    }
+
+    pub fn load(&self) {
+        self.webview.load_uri(self.url.as_str());
+    }
+
+    // keep window.www in line with the widget, for the current page and the next loads
+    fn sync_javascript(&mut self) {
+        let current = serde_json::to_value(&*self).unwrap();
+
+        if let Some(script) = self.user_script.take() {
+            self.webview
+                .user_content_manager()
+                .unwrap()
+                .remove_script(&script);
+        }
+        self.user_script = Some(inject_javascript_to_webview(&self.webview, &current));
+
+        if let Some(diff) = json_diff(&self.snapshot, &current) {
+            let script = r#"
+                window.www = Object.assign(window.www || {}, {{www}});
+                window.dispatchEvent(new CustomEvent("www:change", { detail: {{diff}} }));
+            "#
+            .replace("{{www}}", serde_json::to_string(&current).unwrap().as_str())
+            .replace("{{diff}}", serde_json::to_string(&diff).unwrap().as_str());
+
+            self.webview
+                .run_javascript(script.as_str(), gdk::gio::Cancellable::NONE, |_| {});
+        }
+
+        self.snapshot = current;
     }
 
-    pub fn hide(&self) {
-        self.window.hide();
+    // follow the content size reported by the page on the auto sized axes
+    pub fn fit_content(&mut self, width: i32, height: i32) {
+        self.content_size = Some((width, height));
+
+        if self.refit() {
+            self.sync_autohide();
+            self.sync_javascript();
+        }
+    }
+
+    // size the window to the reported content on the auto sized axes and to the configured size
+    // elsewhere, or back to the configured size once auto sizing is off, returns whether it changed
+    fn refit(&mut self) -> bool {
+        let (width, height) = self
+            .metadata
+            .size
+            .as_ref()
+            .map_or((-1, -1), |e| (e.width, e.height));
+
+        let (Some(auto_size), Some((content_width, content_height))) =
+            (self.metadata.auto_size, self.content_size)
+        else {
+            if self.metadata.fitted_size.take().is_none() {
+                return false;
+            }
+            self.window.set_size_request(width, height);
+            self.window.resize(width.max(1), height.max(1));
+            return true;
+        };
+        let limits = self.metadata.size_limits.as_ref();
+        let mut size = WidgetMetadataFittedSize { width, height };
+
+        // the page reports css pixels
+        if matches!(auto_size, WidgetAutoSize::Width | WidgetAutoSize::Both) {
+            size.width = clamp_size(
+                (content_width as f64 * self.metadata.zoom_level).ceil() as i32,
+                limits.and_then(|e| e.min_width),
+                limits.and_then(|e| e.max_width),
+            );
+        }
+        if matches!(auto_size, WidgetAutoSize::Height | WidgetAutoSize::Both) {
+            size.height = clamp_size(
+                (content_height as f64 * self.metadata.zoom_level).ceil() as i32,
+                limits.and_then(|e| e.min_height),
+                limits.and_then(|e| e.max_height),
+            );
+        }
+
+        // applied even when unchanged, resolving the configured size resets the size request
+        self.window.set_size_request(size.width, size.height);
+        // a layer surface keeps its size unless asked to shrink
+        self.window.resize(size.width.max(1), size.height.max(1));
+
+        let changed = self.metadata.fitted_size != Some(size);
+        self.metadata.fitted_size = Some(size);
+        changed
+    }
+
+    // rebuild the autohide state from the metadata, keeping whether it is revealed
+    fn sync_autohide(&mut self) {
+        let edge = self.metadata.anchors.as_ref().and_then(autohide_edge);
+        let mut state = self.autohide.borrow_mut();
+
+        let Some((autohide, edge)) = self.metadata.autohide.as_ref().zip(edge) else {
+            // restore the configured window once autohide is turned off
+            if let Some(mut previous) = state.take() {
+                previous.revealed = true;
+                apply_autohide(&self.window, &previous);
+                update_exclusive(&self.window, self.metadata.exclusive);
+                update_input_region(
+                    &self.window,
+                    self.metadata.click_through,
+                    self.input_rectangles(),
+                );
+            }
+            return;
+        };
+
+        let margins = self.metadata.margins.as_ref();
+        let margin = margins.map_or(0, |e| match edge {
+            Edge::Top => e.top,
+            Edge::Right => e.right,
+            Edge::Bottom => e.bottom,
+            _ => e.left,
+        });
+        let (width, height) = match (&self.metadata.fitted_size, &self.metadata.size) {
+            (Some(e), _) => (e.width, e.height),
+            (None, Some(e)) => (e.width, e.height),
+            (None, None) => (-1, -1),
+        };
+        let previous = state.as_ref();
+
+        let next = Autohide {
+            mode: autohide.mode,
+            edge,
+            size: autohide.size,
+            delay: Duration::from_millis(autohide.delay),
+            margin,
+            width,
+            height,
+            revealed: previous.is_some_and(|e| e.revealed),
+            generation: previous.map_or(0, |e| e.generation + 1),
+            click_through: self.metadata.click_through,
+            interactive: self.input_rectangles().cloned(),
+        };
+
+        apply_autohide(&self.window, &next);
+        // reserve the strip only, the revealed widget overlaps other windows
+        if self.metadata.exclusive {
+            self.window.set_exclusive_zone(next.size);
+        }
+
+        *state = Some(next);
+    }
+
+    pub fn set_input_region(&mut self, rects: Option<&[InputRect]>) {
+        // the page measures in css pixels
+        let zoom = self.metadata.zoom_level;
+        self.interactive =
+            rects.map(|rects| rects.iter().map(|e| zoom_rectangle(e, zoom)).collect());
+
+        self.sync_input_region();
+    }
+
+    pub fn set_opaque_region(&mut self, rects: &[InputRect]) {
+        // a snapshot may arrive after passthrough was turned off
+        if !self.metadata.alpha_passthrough {
+            return;
+        }
+
+        self.opaque = Some(
+            rects
+                .iter()
+                .map(|e| RectangleInt::new(e.x, e.y, e.width, e.height))
+                .collect(),
+        );
+
+        self.sync_input_region();
+    }
+
+    // the configured input region, autohide keeps its strip taking input on top of it
+    fn sync_input_region(&self) {
+        match self.autohide.borrow_mut().as_mut() {
+            Some(state) => {
+                state.click_through = self.metadata.click_through;
+                state.interactive = self.input_rectangles().cloned();
+                update_autohide_input_region(&self.window, state);
+            }
+            None => update_input_region(
+                &self.window,
+                self.metadata.click_through,
+                self.input_rectangles(),
+            ),
+        }
+    }
+
+    // the opaque pixels while alpha passthrough is on, else the elements marked by the page
+    fn input_rectangles(&self) -> Option<&Vec<RectangleInt>> {
+        match self.metadata.alpha_passthrough {
+            true => self.opaque.as_ref(),
+            false => self.interactive.as_ref(),
+        }
     }
 
     pub fn close(&self) {
@@ -295,51 +1147,296 @@ impl Widget {
         inspector.show();
     }
 
-    pub fn update(&mut self, metadata: &WidgetMetadataArgs) {
-        if let Some(monitor) = metadata.monitor {
-            self.metadata.monitor = Some(update_monitor(&self.window, monitor));
+    pub fn emit<T: Serialize>(&self, topic: &str, detail: &T) {
+        dispatch_event(&self.webview, topic, detail);
+    }
+
+    // like emit, the page also keeps the detail for callbacks subscribing later
+    pub fn emit_retained<T: Serialize>(&self, topic: &str, detail: &T) {
+        let retain = serde_json::json!({ "topic": topic, "detail": detail });
+        dispatch_event(&self.webview, "retain", &retain);
+        dispatch_event(&self.webview, topic, detail);
+    }
+
+    // the directory relative to ~/.config/www/ the widget was created from
+    pub fn directory(&self) -> Option<&str> {
+        self.url
+            .strip_prefix(WIDGET_SERVER_URL)?
+            .strip_prefix('/')?
+            .strip_suffix("/index.html")
+    }
+
+    pub fn load_state(&self) -> &'static str {
+        if self.load_failed.get() {
+            "failed"
+        } else if self.webview.is_loading() {
+            "loading"
+        } else {
+            "loaded"
+        }
+    }
+
+    // the columns of `list`, also available to `list --format` templates
+    pub fn columns(&self) -> Vec<(&'static str, String)> {
+        COLUMNS
+            .into_iter()
+            .zip([
+                self.name.to_owned().unwrap_or("-".to_string()),
+                self.id.to_owned(),
+                self.directory().unwrap_or(self.url.as_str()).to_string(),
+                self.tags.join(","),
+                self.metadata
+                    .layer
+                    .map_or("-".to_string(), |e| e.to_string()),
+                self.metadata.output.to_owned().unwrap_or("-".to_string()),
+                self.visible.to_string(),
+                self.load_state().to_string(),
+                match self.permissions.is_empty() {
+                    true => "-".to_string(),
+                    false => self
+                        .permissions
+                        .iter()
+                        .map(|e| e.to_string())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                },
+            ])
+            .collect()
+    }
+
+    // the id followed by the name, the visibility and the effective grants
+    pub fn summary(&self) -> String {
+        let permissions = self
+            .permissions
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let visibility = match self.visible {
+            true => "visible",
+            false => "hidden",
+        };
+
+        format!(
+            "{}\t{}\t{}\t{}",
+            self.id,
+            self.name.as_deref().unwrap_or("-"),
+            visibility,
+            permissions
+        )
+    }
+
+    // resolve the monitor selector again, e.g. after the set of monitors changed,
+    // widgets without one still follow the monitor the compositor likely picks
+    pub fn place(&mut self, focused: Option<&str>) {
+        self.sync_monitor(focused);
+        self.sync_zoom();
+        self.resolve_lengths();
+        self.sync_autohide();
+
+        if self.placed && self.visible && !self.window.is_visible() {
+            self.window.show_all();
+        } else if !self.placed {
+            self.window.hide();
+        }
+
+        self.sync_javascript();
+    }
+
+    fn sync_monitor(&mut self, focused: Option<&str>) {
+        let display = &Display::default().expect("failed to get display");
+        let monitor = match self.metadata.monitor.as_deref() {
+            Some("all") => self.pinned.clone(),
+            Some(selector) => resolve_monitor(display, selector, focused),
+            None => return,
+        };
+
+        // leave the window unmapped until a matching monitor shows up
+        self.placed = monitor.is_some();
+        self.metadata.output = monitor
+            .as_ref()
+            .and_then(|e| update_monitor(&self.window, e));
+        self.monitor = monitor;
+    }
+
+    // the resolved monitor, or where the compositor most likely puts the window
+    fn target_monitor(&self) -> Option<Monitor> {
+        let display = Display::default().expect("failed to get display");
+
+        self.monitor
+            .clone()
+            .or(display.primary_monitor())
+            .or(display.monitor(0))
+    }
+
+    // logical pixels per inch of the monitor relative to the 96 pages are designed for
+    fn monitor_density(&self) -> f64 {
+        let Some(monitor) = self.target_monitor() else {
+            return 1.0;
+        };
+
+        match monitor.width_mm() {
+            0 => 1.0,
+            width_mm => monitor.geometry().width() as f64 / (width_mm as f64 / 25.4) / 96.0,
+        }
+    }
+
+    // the widget's zoom, times the monitor density and the global ui scale when asked to
+    fn sync_zoom(&mut self) {
+        let mut zoom = self.metadata.zoom;
+
+        if self.metadata.zoom_dpi {
+            zoom *= self.monitor_density();
+        }
+        if self.metadata.follow_ui_scale {
+            zoom *= self.ui_scale;
+        }
+
+        if zoom == self.metadata.zoom_level {
+            return;
+        }
+
+        self.webview.set_zoom_level(zoom);
+        self.metadata.zoom_level = zoom;
+
+        // the content size is reported in css pixels
+        if self.refit() {
+            self.sync_autohide();
+        }
+    }
+
+    pub fn set_ui_scale(&mut self, scale: f64) {
+        self.ui_scale = scale;
+        self.sync_zoom();
+        self.sync_javascript();
+    }
+
+    // the width and height percentages resolve against
+    fn monitor_extent(&self) -> (i32, i32) {
+        self.target_monitor().map_or((0, 0), |e| {
+            let geometry = e.geometry();
+            (geometry.width(), geometry.height())
+        })
+    }
+
+    // resolve requested margins and size again, after the monitor or its scale changed
+    fn resolve_lengths(&mut self) {
+        let extent = self.monitor_extent();
+
+        if let Some(margins) = self.metadata.margins.take() {
+            self.metadata.margins = Some(update_margins(&self.window, &margins.requested, extent));
+        }
+        if let Some(size) = self.metadata.size.take() {
+            self.metadata.size = Some(update_size(&self.window, &size.requested, extent));
+        }
+        // auto sized axes follow the content again
+        self.refit();
+    }
+
+    pub fn update(&mut self, metadata: &WidgetMetadataArgs, focused: Option<&str>) {
+        if let Some(monitor) = metadata.monitor.as_ref() {
+            self.metadata.monitor = Some(monitor.to_owned());
+            self.sync_monitor(focused);
+            self.resolve_lengths();
         }
         if let Some(layer) = metadata.layer.as_ref() {
-            self.metadata.layer = Some(update_layer(&self.window, layer.to_owned()));
+            self.metadata.layer = Some(update_layer(&self.window, *layer));
         }
         if let Some(margins) = &metadata.margins {
-            self.metadata.margins = Some(update_margins(&self.window, margins));
+            let margins = merge_margins(self.metadata.margins.as_ref(), margins);
+            self.metadata.margins = Some(update_margins(
+                &self.window,
+                &margins,
+                self.monitor_extent(),
+            ));
         }
         if let Some(anchors) = &metadata.anchors {
             self.metadata.anchors = Some(update_anchors(&self.window, anchors));
         }
         if let Some(size) = &metadata.size {
-            self.metadata.size = Some(update_size(&self.window, size));
+            let size = merge_size(self.metadata.size.as_ref(), size);
+            self.metadata.size = Some(update_size(&self.window, &size, self.monitor_extent()));
+        }
+        if let Some(auto_size) = metadata.auto_size {
+            self.metadata.auto_size = match auto_size {
+                WidgetAutoSize::None => None,
+                _ => Some(auto_size),
+            };
+        }
+        if let Some(limits) = &metadata.size_limits {
+            self.metadata.size_limits = Some(update_size_limits(limits));
         }
         if let Some(click_through) = metadata.click_through {
-            self.metadata.click_through = update_click_through(&self.window, click_through);
+            self.metadata.click_through = click_through;
+            self.sync_input_region();
+        }
+        if let Some(alpha_passthrough) = metadata.alpha_passthrough {
+            self.metadata.alpha_passthrough = alpha_passthrough;
+            self.alpha_passthrough.set(alpha_passthrough);
+            self.opaque = None;
+            self.sync_input_region();
+            // the next repaint takes the first snapshot
+            self.webview.queue_draw();
         }
         if let Some(exclusive) = metadata.exclusive {
-            self.metadata.click_through = update_exclusive(&self.window, exclusive);
+            self.metadata.exclusive = update_exclusive(&self.window, exclusive);
+        }
+        if let Some(autohide) = &metadata.autohide {
+            self.metadata.autohide = update_autohide(self.metadata.autohide.as_ref(), autohide);
+        }
+        self.sync_autohide();
+        if let Some(transition) = &metadata.transition {
+            self.metadata.transition =
+                update_transition(self.metadata.transition.as_ref(), transition);
         }
         if let Some(keyboard_mode) = metadata.keyboard_mode.as_ref() {
-            self.metadata.keyboard_mode =
-                Some(update_keyboard_mode(&self.window, keyboard_mode.to_owned()));
+            self.metadata.keyboard_mode = Some(update_keyboard_mode(&self.window, *keyboard_mode));
+        }
+        if let Some(zoom) = metadata.zoom {
+            self.metadata.zoom = zoom;
         }
-        if let Some(signal) = self.signal_handler.take() {
-            self.webview.disconnect(signal);
+        if let Some(zoom_dpi) = metadata.zoom_dpi {
+            self.metadata.zoom_dpi = zoom_dpi;
         }
-        self.signal_handler = Some(inject_javascript_to_webview(&self));
+        if let Some(follow_ui_scale) = metadata.follow_ui_scale {
+            self.metadata.follow_ui_scale = follow_ui_scale;
+        }
+        self.sync_zoom();
+        // the configured size, limits or zoom may have changed under the fitted size
+        if self.refit() {
+            self.sync_autohide();
+        }
+        self.sync_javascript();
     }
 
     pub fn new(
         app: &Application,
         url: String,
         tags: Vec<String>,
-        api: async_std::channel::Sender<String>,
+        permissions: Vec<Capability>,
+        api: async_std::channel::Sender<WidgetMessage>,
     ) -> Self {
+        let id = Uuid::new_v4().to_string();
         let window = create_window(app);
-        let webview = create_webview(url.to_owned());
+        let webview = create_webview();
         window.add(&webview);
         // init gtk layer shell
         apply_layer_shell(&window);
         // inject ipc
-        apply_javascript_api(&webview, api);
+        apply_javascript_api(&webview, id.to_owned(), permissions.to_owned(), api.clone());
+        // off until enabled by update
+        let alpha_passthrough = Rc::new(Cell::new(false));
+        apply_alpha_passthrough(
+            &webview,
+            id.to_owned(),
+            alpha_passthrough.clone(),
+            api.clone(),
+        );
+        let autohide = Rc::new(RefCell::new(None));
+        apply_autohide_handlers(&window, &webview, autohide.clone());
+        let load_failed = Rc::new(Cell::new(false));
+        apply_load_handlers(&webview, id.to_owned(), load_failed.clone(), api);
 
         // enable webkit inspector
         let settings = WebViewExt::settings(&webview).unwrap();
@@ -347,21 +1444,49 @@ impl Widget {
 
         // create widget
         let widget = Self {
-            id: Uuid::new_v4().to_string(),
+            id,
+            name: None,
             tags,
             url,
+            permissions,
             window,
             webview,
-            signal_handler: None,
+            user_script: None,
+            snapshot: serde_json::Value::Null,
+            interactive: None,
+            opaque: None,
+            alpha_passthrough,
+            autohide,
+            transitions: Rc::new(Cell::new(0)),
+            load_failed,
+            content_size: None,
+            subscriptions: HashSet::new(),
+            visible: false,
+            placed: true,
+            group: None,
+            pinned: None,
+            monitor: None,
+            ui_scale: 1.0,
             metadata: WidgetMetadata {
                 monitor: None,
+                output: None,
                 layer: None,
                 margins: None,
                 anchors: None,
                 size: None,
+                auto_size: None,
+                size_limits: None,
+                fitted_size: None,
                 click_through: false,
+                alpha_passthrough: false,
                 exclusive: false,
+                autohide: None,
+                transition: None,
                 keyboard_mode: None,
+                zoom: 1.0,
+                zoom_dpi: false,
+                follow_ui_scale: true,
+                zoom_level: 1.0,
             },
         };
 
@@ -380,22 +1505,46 @@ async fn listen_unix_socket(state: Rc<RefCell<AppState>>) {
         let command = read_socket_response(&mut stream).await;
         let command = serde_json::from_str::<CliCommands>(command.as_str()).unwrap();
 
-        let mut app_state = RefCell::borrow_mut(&state);
-        write_socket_message(&mut stream, command.mutate(&mut app_state)).await;
+        // release the state before awaiting the client, background services borrow it too
+        let (response, settle) = {
+            let mut app_state = RefCell::borrow_mut(&state);
+            let response = command.mutate(&mut app_state);
+            (response, command.settle_time(&app_state))
+        };
+
+        // answer once transitions completed, without holding up other clients
+        glib::spawn_future_local(async move {
+            glib::timeout_future(settle).await;
+            // `{"Ok": ..}` or `{"Err": ..}`, the cli exits with 1 on errors
+            write_socket_message(&mut stream, serde_json::to_string(&response).unwrap()).await;
+        });
     }
 }
 
 async fn listen_webkit_messages(
     state: Rc<RefCell<AppState>>,
-    rx: async_std::channel::Receiver<String>,
+    rx: async_std::channel::Receiver<WidgetMessage>,
 ) {
     while let Ok(message) = rx.recv().await {
-        let command = serde_json::from_str::<CliCommands>(message.as_str()).unwrap();
-        command.mutate(&mut RefCell::borrow_mut(&state));
+        let mut app_state = RefCell::borrow_mut(&state);
+
+        match message.command {
+            ScriptCommands::Bridge(command) => {
+                command.mutate(&message.sender, &mut app_state);
+            }
+            ScriptCommands::Cli(command) => {
+                if let Err(e) = command.mutate(&mut app_state) {
+                    eprintln!("{}", e);
+                }
+            }
+            ScriptCommands::Internal(command) => {
+                command.mutate(&message.sender, &mut app_state);
+            }
+        }
     }
 }
 
-pub fn start_widget_application() {
+pub fn start_widget_application(options: DaemonOptions) {
     gtk::init().unwrap();
 
     let app = Application::new(
@@ -410,8 +1559,13 @@ pub fn start_widget_application() {
         // ipc channel
         let (tx, rx) = channel::unbounded();
         let shared_state = Rc::new(RefCell::new(AppState::new(application.to_owned(), tx)));
+        RefCell::borrow_mut(&shared_state).ui_scale = options.ui_scale;
         let state_for_widget = shared_state.clone();
         let state_for_ipc = shared_state.clone();
+        let state_for_system = shared_state.clone();
+        let state_for_power = shared_state.clone();
+        let state_for_compositor = shared_state.clone();
+        let state_for_network = shared_state.clone();
 
         // listen the socket
         glib::spawn_future_local(async move {
@@ -422,7 +1576,103 @@ pub fn start_widget_application() {
         glib::spawn_future_local(async move {
             listen_webkit_messages(state_for_ipc, rx).await;
         });
+
+        // push system statistics to subscribed widgets
+        start_system_service(
+            state_for_system,
+            options.system_root.to_owned(),
+            options.sys_interval,
+        );
+
+        // publish battery and ac adapter changes
+        start_power_service(
+            state_for_power,
+            options.system_root.to_owned(),
+            options.power_interval,
+        );
+
+        // track workspaces and the focused window of sway or hyprland
+        start_compositor_service(state_for_compositor);
+
+        // follow networkmanager over the system bus
+        start_network_service(state_for_network);
+
+        // publish outputs and re-place widgets as they change
+        start_monitor_service(shared_state.clone());
     });
 
     app.run_with_args::<&str>(&[]);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn length(value: &str) -> Option<Length> {
+        Some(value.parse().unwrap())
+    }
+
+    #[test]
+    fn resolves_margins_against_their_axis() {
+        let margins = WidgetMargins {
+            top: length("10%"),
+            right: length("10%"),
+            bottom: length("calc(50% - 20)"),
+            left: length("calc(50% - 20)"),
+        };
+
+        let ret = resolve_margins(&margins, (1920, 1080));
+
+        // left and right follow the width, top and bottom the height
+        assert_eq!((ret.top, ret.bottom), (108, 520));
+        assert_eq!((ret.left, ret.right), (940, 192));
+        assert_eq!(ret.requested, margins);
+    }
+
+    #[test]
+    fn resolves_size_against_its_axis() {
+        let size = WidgetDefaultSize {
+            width: length("30%"),
+            height: None,
+        };
+
+        let ret = resolve_size(&size, (1920, 1080));
+
+        assert_eq!((ret.width, ret.height), (576, -1));
+
+        let size = WidgetDefaultSize {
+            width: None,
+            height: length("30%"),
+        };
+
+        let ret = resolve_size(&size, (1920, 1080));
+
+        assert_eq!((ret.width, ret.height), (-1, 324));
+    }
+
+    #[test]
+    fn merges_margins_with_the_requested_ones() {
+        let current = resolve_margins(
+            &WidgetMargins {
+                top: length("10"),
+                left: length("25%"),
+                ..Default::default()
+            },
+            (1920, 1080),
+        );
+        let update = WidgetMargins {
+            top: length("20"),
+            right: length("5%"),
+            ..Default::default()
+        };
+
+        let ret = merge_margins(Some(&current), &update);
+
+        // the percentage is kept as requested, to resolve again on another monitor
+        assert_eq!(ret.top, length("20"));
+        assert_eq!(ret.right, length("5%"));
+        assert_eq!(ret.bottom, None);
+        assert_eq!(ret.left, length("25%"));
+        assert_eq!(resolve_margins(&ret, (1000, 500)).left, 250);
+    }
+}
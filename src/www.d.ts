@@ -0,0 +1,87 @@
+// the api www.js adds to window.www, next to the fields of the serialized widget
+
+/** stops the callback, the last one of a topic also unsubscribes the page */
+export type Unsubscribe = () => void;
+
+export interface StorageOptions {
+  /** the namespace shared by every widget instead of this widget's own */
+  global?: boolean;
+}
+
+export interface Www extends Widget {
+  post(message: ScriptCommands): void;
+
+  /** a posted command needed a capability the widget was not granted */
+  onDenied(callback: (denied: { capability: Capability; call: number | null }) => void): Unsubscribe;
+
+  /** manage the widget's own window, other widgets need the other-widgets permission */
+  self: {
+    resize(width: Length, height: Length): void;
+    setMargins(margins: WidgetMargins): void;
+    setAnchors(anchors: Array<WidgetAnchor>): void;
+    setClickThrough(enabled: boolean): void;
+    setAlphaPassthrough(enabled: boolean): void;
+    setKeyboardMode(mode: WidgetKeyboardMode): void;
+    setZoom(zoom: number): void;
+    setAutoSize(mode: NonNullable<WidgetMetadataArgs["auto_size"]>, limits?: WidgetSizeLimits): void;
+    setAutohide(
+      mode: NonNullable<WidgetAutohide["mode"]>,
+      options?: { size?: number; delay?: number },
+    ): void;
+    onAutohide(callback: (state: { revealed: boolean }) => void): Unsubscribe;
+    setTransition(
+      kind: NonNullable<WidgetTransition["kind"]>,
+      options?: { duration?: number; easing?: string },
+    ): void;
+    onVisibility(callback: (state: { visible: boolean }) => void): Unsubscribe;
+    hide(): void;
+    close(): void;
+  };
+
+  sys: {
+    subscribe(callback: (stats: SystemStats) => void): Unsubscribe;
+  };
+
+  /** every widget shown or hidden */
+  visibility: {
+    subscribe(callback: (change: { id: string; visible: boolean }) => void): Unsubscribe;
+  };
+
+  monitors: {
+    subscribe(callback: (monitors: Array<MonitorInfo>) => void): Unsubscribe;
+  };
+
+  power: {
+    subscribe(callback: (status: PowerStatus) => void): Unsubscribe;
+  };
+
+  compositor: {
+    subscribe(callback: (state: CompositorState) => void): Unsubscribe;
+    switchWorkspace(name: string | number): void;
+  };
+
+  bus: {
+    publish(channel: string, data: unknown, options?: { retain?: boolean }): void;
+    subscribe(channel: string, callback: (data: unknown) => void): Unsubscribe;
+  };
+
+  storage: {
+    get(key: string, options?: StorageOptions): Promise<unknown>;
+    entries(options?: StorageOptions): Promise<Record<string, unknown>>;
+    set(key: string, value: unknown, options?: StorageOptions): void;
+    remove(key: string, options?: StorageOptions): void;
+    subscribe(callback: (change: StorageChange) => void, options?: StorageOptions): Unsubscribe;
+  };
+
+  network: {
+    subscribe(callback: (status: NetworkStatus) => void): Unsubscribe;
+    setWirelessEnabled(enabled: boolean): void;
+    activateConnection(connection: string): void;
+  };
+}
+
+declare global {
+  interface Window {
+    www: Www;
+  }
+}
@@ -0,0 +1,261 @@
+// page side of the javascript bridge, injected into every widget at document start
+(() => {
+  const www = (window.www = window.www || {});
+
+  const post = (message) =>
+    window.webkit.messageHandlers.widget.postMessage(JSON.stringify(message));
+
+  const listen = (topic, callback) => {
+    const handler = (event) => callback(event.detail);
+    window.addEventListener(`www:${topic}`, handler);
+    return () => window.removeEventListener(`www:${topic}`, handler);
+  };
+
+  // the daemon only tracks whether a page is subscribed, so count callbacks here
+  const subscribers = {};
+  // the daemon replays retained values on `Subscribe` only, later callbacks get them from here
+  const retained = {};
+
+  listen("retain", ({ topic, detail }) => {
+    if (subscribers[topic]) {
+      retained[topic] = detail;
+    }
+  });
+
+  // subscribe to a topic pushed by the daemon, returns a function to unsubscribe
+  const subscribe = (topic, callback) => {
+    const unlisten = listen(topic, callback);
+    subscribers[topic] = (subscribers[topic] || 0) + 1;
+    if (subscribers[topic] === 1) {
+      post({ Subscribe: { topic } });
+    } else if (topic in retained) {
+      callback(retained[topic]);
+    }
+    return () => {
+      unlisten();
+      subscribers[topic] -= 1;
+      if (subscribers[topic] === 0) {
+        delete retained[topic];
+        post({ Unsubscribe: { topic } });
+      }
+    };
+  };
+
+  // commands wrapped in a `Call` are answered with a `www:reply` event
+  const pending = {};
+  let calls = 0;
+
+  listen("reply", ({ id, result }) => {
+    pending[id]?.resolve(result);
+    delete pending[id];
+  });
+
+  // commands needing a capability the widget was not granted are dropped by the daemon
+  listen("denied", ({ capability, call }) => {
+    pending[call]?.reject(new Error(`missing capability: ${capability}`));
+    delete pending[call];
+  });
+
+  const call = (command) =>
+    new Promise((resolve, reject) => {
+      calls += 1;
+      pending[calls] = { resolve, reject };
+      post({ Call: { id: calls, command } });
+    });
+
+  www.post = post;
+
+  // `www.permissions` holds the granted capabilities, serialized with the widget
+  www.onDenied = (callback) => listen("denied", callback);
+
+  // manage the widget's own window, other widgets need the other-widgets permission
+  const updateSelf = (metadata) => post({ UpdateSelf: { metadata } });
+
+  www.self = {
+    resize: (width, height) => updateSelf({ size: { width, height } }),
+    setMargins: (margins) => updateSelf({ margins }),
+    setAnchors: (anchors) => updateSelf({ anchors }),
+    setClickThrough: (enabled) => updateSelf({ click_through: Boolean(enabled) }),
+    setAlphaPassthrough: (enabled) => updateSelf({ alpha_passthrough: Boolean(enabled) }),
+    setKeyboardMode: (mode) => updateSelf({ keyboard_mode: mode }),
+    setZoom: (zoom) => updateSelf({ zoom: Number(zoom) }),
+    // "none", "width", "height" or "both", limits as { min_width, max_width, min_height, max_height }
+    setAutoSize: (mode, limits) => updateSelf({ auto_size: mode, size_limits: limits }),
+    // "none", "collapse" or "slide", see `--autohide`
+    setAutohide: (mode, { size, delay } = {}) => updateSelf({ autohide: { mode, size, delay } }),
+    onAutohide: (callback) => listen("autohide", callback),
+    // "none", "fade", "slide" or "scale", easing is any css easing function
+    setTransition: (kind, { duration, easing } = {}) =>
+      updateSelf({ transition: { kind, duration, easing } }),
+    // called with { visible } whenever the widget is shown or hidden
+    onVisibility: (callback) => listen("self:visibility", callback),
+    hide: () => post("HideSelf"),
+    close: () => post("CloseSelf"),
+  };
+
+  // play the show and hide transitions on the root element, the daemon unmaps the window after
+  const offscreen = {
+    top: "translateY(-100%)",
+    right: "translateX(100%)",
+    bottom: "translateY(100%)",
+    left: "translateX(-100%)",
+  };
+
+  listen("transition", ({ phase, kind, duration, easing, edge }) => {
+    const root = document.documentElement;
+    const shown = { opacity: "", transform: "" };
+    const hidden = {
+      fade: { opacity: "0" },
+      slide: { transform: offscreen[edge] },
+      scale: { opacity: "0", transform: "scale(0.9)" },
+    }[kind];
+
+    root.style.transition = "none";
+    Object.assign(root.style, phase === "show" ? { ...shown, ...hidden } : shown);
+    // commit the starting state before transitioning from it
+    root.getBoundingClientRect();
+    root.style.transition = `opacity ${duration}ms ${easing}, transform ${duration}ms ${easing}`;
+    Object.assign(root.style, phase === "show" ? shown : { ...shown, ...hidden });
+  });
+
+  // while auto sized, report the content size so the daemon can fit the window to it
+  let sizeObserver = null;
+
+  const autoSize = () => {
+    const mode = www.metadata?.auto_size;
+    const root = document.documentElement;
+
+    sizeObserver?.disconnect();
+    sizeObserver = null;
+    // a block element fills the viewport, so let the content decide the width instead
+    root.style.width = mode === "width" || mode === "both" ? "max-content" : "";
+    if (!mode) {
+      return;
+    }
+
+    sizeObserver = new ResizeObserver(([entry]) => {
+      const [size] = entry.borderBoxSize;
+      post({
+        ContentSize: { width: Math.ceil(size.inlineSize), height: Math.ceil(size.blockSize) },
+      });
+    });
+    sizeObserver.observe(root);
+  };
+
+  // window.www is filled in after this script, see `inject_javascript_to_webview`
+  document.addEventListener("DOMContentLoaded", autoSize);
+  listen("change", ({ metadata }) => {
+    if (metadata && "auto_size" in metadata && document.readyState !== "loading") {
+      autoSize();
+    }
+  });
+
+  // keep the window's input region on the elements marked `data-www-interactive`
+  let regionFrame = null;
+  let lastRegion;
+  const regionObserver = new ResizeObserver(() => reportRegion());
+
+  const reportRegion = () => {
+    if (regionFrame !== null) {
+      return;
+    }
+
+    // coalesce layout changes and scrolling into one message per frame
+    regionFrame = requestAnimationFrame(() => {
+      regionFrame = null;
+
+      const elements = [...document.querySelectorAll("[data-www-interactive]")];
+      const rects =
+        elements.length === 0
+          ? null
+          : elements
+              .flatMap((e) => [...e.getClientRects()])
+              .filter((e) => e.width > 0 && e.height > 0)
+              .map((e) => ({
+                x: Math.floor(e.left),
+                y: Math.floor(e.top),
+                width: Math.ceil(e.right) - Math.floor(e.left),
+                height: Math.ceil(e.bottom) - Math.floor(e.top),
+              }));
+
+      const region = JSON.stringify(rects);
+      if (region !== lastRegion) {
+        lastRegion = region;
+        post({ InputRegion: { rects } });
+      }
+    });
+  };
+
+  const observeRegion = () => {
+    regionObserver.disconnect();
+    document.querySelectorAll("[data-www-interactive]").forEach((e) => regionObserver.observe(e));
+    reportRegion();
+  };
+
+  // the daemon scales the region by the zoom it was posted at, send it again at the new one
+  listen("change", ({ metadata }) => {
+    if (metadata && "zoom_level" in metadata) {
+      lastRegion = undefined;
+      reportRegion();
+    }
+  });
+
+  document.addEventListener("DOMContentLoaded", () => {
+    new MutationObserver(observeRegion).observe(document.documentElement, {
+      subtree: true,
+      childList: true,
+      attributes: true,
+    });
+    window.addEventListener("scroll", reportRegion, { capture: true, passive: true });
+    window.addEventListener("resize", reportRegion);
+    observeRegion();
+  });
+
+  www.sys = {
+    subscribe: (callback) => subscribe("sys", callback),
+  };
+
+  // { id, visible } of every widget shown or hidden
+  www.visibility = {
+    subscribe: (callback) => subscribe("visibility", callback),
+  };
+
+  www.monitors = {
+    // every output with its connector, geometry, workarea, scale factor and refresh rate
+    subscribe: (callback) => subscribe("monitors", callback),
+  };
+
+  www.power = {
+    subscribe: (callback) => subscribe("power", callback),
+  };
+
+  www.compositor = {
+    subscribe: (callback) => subscribe("compositor", callback),
+    switchWorkspace: (name) => post({ SwitchWorkspace: { name: String(name) } }),
+  };
+
+  www.bus = {
+    publish: (channel, data, { retain = false } = {}) =>
+      post({ Publish: { channel, data, retain } }),
+    subscribe: (channel, callback) => subscribe(`bus:${channel}`, callback),
+  };
+
+  www.storage = {
+    get: (key, { global = false } = {}) =>
+      call({ StorageGet: { key, global } }).then((e) => JSON.parse(e)),
+    entries: ({ global = false } = {}) =>
+      call({ StorageGet: { key: null, global } }).then((e) => JSON.parse(e)),
+    set: (key, value, { global = false } = {}) =>
+      post({ StorageSet: { key, value: value === undefined ? null : value, global } }),
+    remove: (key, { global = false } = {}) => post({ StorageSet: { key, value: null, global } }),
+    // notified when this widget's or the global namespace changes, from any widget or the cli
+    subscribe: (callback, { global = false } = {}) =>
+      subscribe(global ? "storage:global" : "storage", callback),
+  };
+
+  www.network = {
+    subscribe: (callback) => subscribe("network", callback),
+    setWirelessEnabled: (enabled) => post({ SetWirelessEnabled: { enabled: Boolean(enabled) } }),
+    activateConnection: (connection) => post({ ActivateConnection: { connection } }),
+  };
+})();